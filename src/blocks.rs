@@ -9,6 +9,7 @@
 //! `if_command` | Only display the block if the supplied command returns 0 on startup. | None
 //! `merge_with_next` | If true this will group the block with the next one, so rendering such as alternating_tint will apply to the whole group | `false`
 //! `icons_format` | Overrides global `icons_format` | None
+//! `decimal_separator` | Overrides global `decimal_separator` | None
 //! `error_format` | Overrides global `error_format` | None
 //! `error_fullscreen_format` | Overrides global `error_fullscreen_format` | None
 //! `error_interval` | How long to wait until restarting the block after an error occurred. | `5`
@@ -2,23 +2,57 @@
 //!
 //! This block displays local weather and temperature information. In order to use this block, you
 //! will need access to a supported weather API service. At the time of writing, OpenWeatherMap,
-//! met.no, and the US National Weather Service are supported.
+//! met.no, Open-Meteo and the US National Weather Service are supported.
 //!
 //! Configuring this block requires configuring a weather service, which may require API keys and
 //! other parameters.
 //!
 //! If using the `autolocate` feature, set the autolocate update interval such that you do not exceed ipapi.co's free daily limit of 1000 hits. Or use `autolocate_interval = "once"` to only run on initialization.
 //!
+//! While the bar is hidden, i3bar and swaybar pause i3status-rust, so no requests are made. If an
+//! update fell due in the meantime, it is made as soon as the bar is shown again. This doesn't
+//! apply when running with `--never-pause`.
+//!
 //! # Configuration
 //!
 //! Key | Values | Default
 //! ----|--------|--------
 //! `service` | The configuration of a weather service (see below). | **Required**
 //! `format` | A string to customise the output of this block. See below for available placeholders. Text may need to be escaped, refer to [Escaping Text](#escaping-text). | `" $icon $weather $temp "`
+//! `format_icon` | If `true` and `format` isn't set, only the icon and the temperature are shown, with the format `" $icon $temp "` | `false`
 //! `format_alt` | If set, block will switch between `format` and `format_alt` on every click | `None`
+//! `views` | Additional formats shown in turn after `format` by the `cycle_view` action, e.g. `[" $temp_fmin - $temp_fmax ", " $icon_strip "]`. Switching views doesn't update the weather. | `[]`
+//! `icons` | Text replacing the icons of some weather conditions, e.g. `{ Snow = "❄" }`. See [Used Icons](#used-icons). | `{}`
+//! `fallback` | A list of further weather services, tried in order when `service` fails. | `[]`
+//! `proxy` | URL of an HTTP or HTTPS proxy, e.g. `"http://proxy.example:3128"`, used for the requests of this block only. SOCKS proxies are not supported. | `None`
+//! `provider_timeout` | How long to wait for each weather service, in seconds, before trying the next one. | `60`
 //! `interval` | Update interval, in seconds. | `600`
+//! `refresh_cooldown` | Minimum time, in seconds, between two updates requested with the `refresh` action | `30`
+//! `jitter` | Each update is moved by a random amount of up to this many seconds, so that many bars sharing an IP address do not all query the service at once. | `5`
 //! `autolocate` | Gets your location using the ipapi.co IP location service (no API key required). If the API call fails then the block will fallback to service specific location config. | `false`
 //! `autolocate_interval` | Update interval for `autolocate` in seconds or "once" | `interval`
+//! `city` | Name of a city whose weather is shown, e.g. `"Berlin"`, looked up with the Open-Meteo geocoding API (no API key required). The first match is used as if it was autolocated, and is cached on disk. Cannot be combined with `autolocate` or the `coordinates` of the services. | `None`
+//! `temp_precision` | If set, temperatures are rounded to this many decimal places | `None`
+//! `wind_precision` | If set, wind speeds are rounded to this many decimal places | `None`
+//! `wind_calm_symbol` | Shown by `wind_arrow` when the wind has no direction, e.g. when it is calm | `""`
+//! `max_verbose_len` | If set, `weather_verbose` and `weather_detailed` are truncated to this many grapheme clusters, ending with "…" | `None`
+//! `freeze_below` | If set, the block is shown in the critical state and the `is_freezing` flag is set when the temperature is below this value, in the configured units | `None`
+//! `warn_on` | Weather conditions that show the block in the warning state, e.g. `["thunder", "snow"]`. The conditions are `clear`, `clouds`, `partly_cloudy`, `fog`, `rain`, `sleet`, `snow` and `thunder`. | `[]`
+//! `critical_on` | Weather conditions that show the block in the critical state, like `warn_on` | `[]`
+//! `heat_above` | If set, the block is shown in the critical state and the `is_hot` flag is set when the temperature is above this value, in the configured units | `None`
+//! `gust_warn_above` | If set, the block is shown in the warning state and the `is_gusty` flag is set when the current wind gusts, or the strongest forecast ones, are above this speed, in km/h or mph depending on the units of the service | `None`
+//! `temp_color` | Color the block by the temperature, using the thresholds of `comfort_levels`: the info state when freezing or cold, good when cool or mild, warning when warm and critical when hot. The states set by the other options take precedence. | `false`
+//! `icon_strip_len` | Maximum number of forecast periods shown by `icon_strip` | `6`
+//! `trend_horizon` | Number of forecast periods ahead the `temp_trend` key compares the temperature with. If unset, or the service provides no individual periods, the final forecast value is used. | `None`
+//! `trend_threshold` | Minimum temperature change, in degrees, for `temp_trend` to show a rise or fall | `1.0`
+//! `comfort_levels` | Thresholds of the `comfort` key: `freezing_below`, `cold_below`, `cool_below`, `warm_above` and `hot_above`, in the `units` of the table (`"metric"` or `"imperial"`) | `{ units = "metric", freezing_below = 0, cold_below = 10, cool_below = 18, warm_above = 24, hot_above = 30 }`
+//! `humidity_levels` | Thresholds of the `humidity_level` key, in %: `dry_below`, `humid_above` and `oppressive_above` | `{ dry_below = 30, humid_above = 60, oppressive_above = 80 }`
+//! `location_name` | If set, shown as `location` instead of the name reported by the service | `None`
+//! `max_failures` | How many updates in a row may fail before the error is shown. Until then, the last data is kept and marked with `is_stale`. | `3`
+//! `stale_after` | If the forecast was last updated longer ago than this many seconds, the block is shown in the warning state and the `is_stale` flag is set. Only supported by services reporting an update time. | `None`
+//!
+//! Numbers are shown with the `decimal_separator` of the block, or the global one, e.g.
+//! `decimal_separator = ","` to show `18,5°` instead of `18.5°`.
 //!
 //! # OpenWeatherMap Options
 //!
@@ -56,32 +90,136 @@
 //!
 //! Met.no does not support location name, but if autolocate is enabled then autolocate's city value is used.
 //!
-//! # US National Weather Service Options
+//! # Open-Meteo Options
 //!
 //! Key | Values | Required | Default
 //! ----|--------|----------|--------
-//! `name` | `nws`. | Yes | None
+//! `name` | `openmeteo`. | Yes | None
 //! `coordinates` | GPS latitude longitude coordinates as a tuple, example: `["39.2362","9.3317"]` | Required if `autolocate = false` | None
 //! `forecast_hours` | How many hours should be forecast | No | 12
 //! `units` | Either `"metric"` or `"imperial"`. | No | `"metric"`
+//! `pressure_unit` | Unit of the pressures: `"hpa"`, `"inhg"` or `"mmhg"` | No | `"hpa"` for metric units, `"inhg"` for imperial units
+//! `aggregate` | How the `_favg` values are computed: `"mean"` or `"median"`. The wind direction is always a vector average. | No | `"mean"`
+//! `aggregate_weighting` | How much each forecast period counts towards the `_favg` means: `"uniform"`, or `"linear_decay"` so that nearer periods count more | No | `"uniform"`
+//! `final_period` | Which period the `_ffin` values are taken from: `"horizon"` for the one at `forecast_hours`, right after the aggregated periods, `"last_aggregated"` for the last of the aggregated periods, or `"none"` to leave the `_ffin` values out | No | `"horizon"`
+//! `extrema_mode` | How the `_fmin` and `_fmax` values are picked: `"per_field"` for the lowest and highest value of each field on its own, or `"per_period"` for all the values of the coldest and the hottest period, so that e.g. `wind_fmin` is the wind when it is coldest | No | `"per_field"`
+//!
+//! Open-Meteo does not support location name, but if autolocate is enabled then autolocate's city value is used.
+//!
+//! # US National Weather Service Options
+//!
+//! Key | Values | Required | Default
+//! ----|--------|----------|--------
+//! `name` | `nws`. | Yes | None
+//! `coordinates` | GPS latitude longitude coordinates as a tuple, example: `["39.2362","9.3317"]`. A single string separated by a comma or a space, such as `"39.2362,9.3317"`, works too. | Required if `autolocate = false`, `locations` is empty and no station or grid point is given | None
+//! `locations` | Additional coordinates to rotate through, example: `[["39.2362","9.3317"], ["40.7128","-74.0060"]]` | No | `[]`
+//! `fallback_coordinates` | Coordinates used when `autolocate` is set but the location can't be found, example: `["39.2362","9.3317"]` | No | None
+//! `station` | Identifier of an observation station, example: `"KBOS"` or `"LOXC1"`. The forecast is shown for the location of the station, and with `use_observations` the observations are taken from it. Cannot be combined with `coordinates`, `locations`, `office` or `autolocate`. | No | None
+//! `office` | Forecast office of a known grid point, example: `"BOX"`. Together with `grid_x` and `grid_y`, this skips resolving coordinates, but cannot be combined with `coordinates`, `locations`, `autolocate` or `show_alerts`. Use the block's `location_name` to name the location. | No | None
+//! `grid_x` | X coordinate of the grid point, example: `71` | No | None
+//! `grid_y` | Y coordinate of the grid point, example: `76` | No | None
+//! `forecast_hours` | How many hours should be forecast. Must be at least 1; leave the forecast keys out of the format to only show the current weather. | No | 12
+//! `location_part` | Which part of the location name to show: `"full"` for the city and state, `"city"` or `"state"` | No | `"full"`
+//! `max_location_len` | Maximum number of grapheme clusters (user-perceived characters) of the location name. Longer names are cut short and end with "…". | No | None
+//! `units` | Either `"metric"` or `"imperial"`. | No | `"metric"`
+//! `temp_unit` | Unit of the temperatures: `"celsius"`, `"fahrenheit"` or `"kelvin"`. Temperatures in kelvins are shown without a degree sign. | No | The one of `units`
+//! `pressure_unit` | Unit of the pressures: `"hpa"`, `"inhg"` or `"mmhg"` | No | `"hpa"` for metric units, `"inhg"` for imperial units
+//! `location_cache_ttl` | How long, in seconds, a resolved forecast grid point is cached on disk before it is resolved again. Each location expires up to a tenth earlier or later, so that they are not all resolved at once. | No | `2592000` (30 days)
+//! `show_alerts` | Fetch active weather alerts for the location, which requires an additional API call | No | `false`
+//! `show_daily_summary` | Fetch the daily forecast too, for the `today_high`, `today_low`, `tomorrow_high` and `tomorrow_low` keys. This takes an additional API call, unless `forecast_mode` is `"daily"`. | No | `false`
+//! `forecast_offset` | Number of forecast periods to skip before the forecast window starts, counting from the period of the current weather. The current weather is not affected by it. | No | `0`
+//! `current_period` | Which period the current weather is taken from: `"first"` for the first one received, or `"nearest_to_now"` for the one containing the current time, which helps when the first hourly period has already ended. The forecast and the `_pN` periods only use the periods from the current one on. | No | `"first"`
+//! `forecast_mode` | Either `"hourly"` or `"daily"`. The daily forecast provides one period for each day and night. | No | `"hourly"`
+//! `retries` | How many times a request is retried after a server error or a timeout | No | `3`
+//! `retry_delay` | Delay, in seconds, before the first retry. The delay doubles after each retry. | No | `1`
+//! `connect_retries` | How many times a request is retried when the server can't be resolved or connected to, e.g. right after resuming from suspend. Independent of `retries`. | No | `2`
+//! `connect_retry_delay` | Delay, in seconds, between retries after a failed connection | No | `2`
+//! `timeout` | How long to wait, in seconds, for each request to complete before giving up on it | No | `10`
+//! `feature_flags` | Feature flags sent with the forecast request, to change the shape of the response. An empty list sends none. | No | `["forecast_wind_speed_qv", "forecast_temperature_qv"]`
+//! `dry_run` | Log the URL and headers of each request at the info level, instead of sending it, to check the configuration. The block then shows an error. As resolving the location of `coordinates` takes a request, only the forecast of a cached location or of a grid point is logged. | No | `false`
+//! `max_requests_per_minute` | Maximum number of requests made to the NWS per minute, together with all the other NWS blocks. Requests beyond it wait for their turn. | No | None
+//! `user_agent` | The `User-Agent` sent with each request. The NWS asks for it to identify the application and include a way to contact you. | No | `"i3status-rust/<version> (https://github.com/greshake/i3status-rust)"`
+//! `api_url` | Base URL of the API, e.g. to use a caching proxy | No | `"https://api.weather.gov/"`
+//! `apparent_temp` | Formula for the apparent temperature: `"australian"`, `"heat_index"`, `"wind_chill"` or `"auto"`, which uses the heat index when hot and humid, the wind chill when cold and windy, and the plain temperature otherwise. The plain temperature is also used when the humidity or the wind speed is missing or out of range. | No | `"australian"`
+//! `apparent` | Compute the apparent temperature. If `false`, `{apparent}` mirrors `{temp}` | No | `true`
+//! `couple_apparent` | Report the `apparent_fmin` and `apparent_fmax` values of the periods with the lowest and highest temperature, instead of the lowest and highest apparent temperatures | No | `false`
+//! `calm_below` | Wind speed, in km/h or mph depending on `units`, below which the wind is considered calm and has no direction. `direction` then shows "-" and `wind_arrow` shows `wind_calm_symbol`. | No | None
+//! `aggregate` | How the `_favg` values are computed: `"mean"` or `"median"`. The wind direction is always a vector average. | No | `"mean"`
+//! `aggregate_weighting` | How much each forecast period counts towards the `_favg` means: `"uniform"`, or `"linear_decay"` so that nearer periods count more | No | `"uniform"`
+//! `final_period` | Which period the `_ffin` values are taken from: `"horizon"` for the one at `forecast_hours`, right after the aggregated periods, `"last_aggregated"` for the last of the aggregated periods, or `"none"` to leave the `_ffin` values out | No | `"horizon"`
+//! `extrema_mode` | How the `_fmin` and `_fmax` values are picked: `"per_field"` for the lowest and highest value of each field on its own, or `"per_period"` for all the values of the coldest and the hottest period, so that e.g. `wind_fmin` is the wind when it is coldest | No | `"per_field"`
+//! `use_api_icon` | Derive the icon from the deprecated `icon` URL of the forecast instead of its short description, if it can be parsed | No | `false`
+//! `use_observations` | Show the latest observation of the nearest weather station as the current weather, instead of the first forecast period. Missing observed values are taken from the forecast. | No | `false`
 //!
 //! Forecasts gather statistics from each hour between now and the `forecast_hours` value, and
-//! provide predicted weather at the set number of hours into the future.
+//! provide predicted weather at the set number of hours into the future. In `daily` mode, each
+//! period covers about 12 hours, so `forecast_hours` is rounded up to a whole number of periods.
+//!
+//! If more than one location is configured, the block moves on to the next location at every
+//! update, and on the `cycle_location` action. Locations are ignored if `autolocate` is enabled.
 //!
 //! # Available Format Keys
 //!
 //!  Key                                         | Value                                                                         | Type   | Unit
 //! ---------------------------------------------|-------------------------------------------------------------------------------|--------|-----
 //! `location`                                   | Location name (exact format depends on the service)                           | Text   | -
+//! `location_distance`                          | Distance from the named location to the coordinates, if reported (NWS only)    | Number | km or miles
+//! `location_bearing`                           | Direction from the named location to the coordinates, e.g. "NE" (NWS only)    | Text   | -
+//! `office`                                     | Forecast office of the location, e.g. "BOX", for the `office` option (NWS only) | Text | -
+//! `grid`                                       | Forecast grid point of the location, e.g. "71,76", for the `grid_x` and `grid_y` options (NWS only) | Text | -
 //! `icon{,_ffin}`                               | Icon representing the weather                                                 | Icon   | -
 //! `weather{,_ffin}`                            | Textual brief description of the weather, e.g. "Raining"                      | Text   | -
 //! `weather_verbose{,_ffin}`                    | Textual verbose description of the weather, e.g. "overcast clouds"            | Text   | -
+//! `weather_detailed{,_ffin}`                   | Detailed forecast text. Empty if unavailable (NWS daily forecasts only)       | Text   | -
 //! `temp{,_{favg,fmin,fmax,ffin}}`              | Temperature                                                                   | Number | degrees
+//! `temp_unit`                                  | Unit of the temperatures: "°C", "°F" or "K"                                   | Text   | -
+//! `pressure_unit`                              | Unit of the pressures: "hPa", "inHg" or "mmHg"                                | Text   | -
+//! `wind_unit`                                  | Unit of the wind speeds: "km/h" or "mph"                                      | Text   | -
+//! `distance_unit`                              | Unit of the distances and visibilities: "km" or "mi"                          | Text   | -
+//! `humidity_unit`                              | Unit of the humidities: "%"                                                   | Text   | -
+//! `temp_min_at`                                | Start of the forecast period with the minimum temperature. Empty if unavailable (NWS and Open-Meteo only) | Datetime | -
+//! `temp_max_at`                                | Start of the forecast period with the maximum temperature. Empty if unavailable (NWS and Open-Meteo only) | Datetime | -
+//! `forecast_count`                             | Number of forecast periods the `_favg`, `_fmin` and `_fmax` values are computed from | Number | -
+//! `today_high`, `today_low`                    | Highest and lowest temperatures of today, from the daily forecast. Today's high is missing in the evening. (NWS only, with `show_daily_summary`) | Number | degrees
+//! `tomorrow_high`, `tomorrow_low`              | Highest and lowest temperatures of tomorrow (NWS only, with `show_daily_summary`) | Number | degrees
+//! `precip_any`                                 | Probability of precipitation in at least one of the forecast periods, taking them as independent (NWS only) | Number | %
+//! `icon_strip`                                 | Symbols of the weather of the upcoming periods, e.g. "☀☁🌧" (NWS only)     | Text   | -
+//! `temp_trend`                                 | "↑" if it is getting warmer, "↓" if it is getting colder, "→" otherwise. Empty if there is no forecast | Text   | -
 //! `apparent{,_{favg,fmin,fmax,ffin}}`          | Australian Apparent Temperature                                               | Number | degrees
 //! `humidity{,_{favg,fmin,fmax,ffin}}`          | Humidity                                                                      | Number | %
-//! `wind{,_{favg,fmin,fmax,ffin}}`              | Wind speed                                                                    | Number | -
+//! `humidity_level`                             | Humidity as "dry", "comfortable", "humid" or "oppressive", see `humidity_levels` | Text   | -
+//! `comfort`                                    | How the current weather feels: "freezing", "cold", "cool", "mild", "warm" or "hot", see `comfort_levels` | Text   | -
+//! `dewpoint{,_{favg,fmin,fmax,ffin}}`          | Dewpoint, if reported (NWS only)                                              | Number | degrees
+//! `pressure{,_{favg,fmin,fmax,ffin}}`          | Barometric pressure, if reported                                              | Number | hPa, inHg or mmHg
+//! `visibility{,_{favg,fmin,fmax,ffin}}`        | Visibility, if reported (Open-Meteo and OpenWeatherMap only)                  | Number | km or miles
+//! `wind{,_{favg,fmin,fmax,ffin}}`              | Wind speed, in km/h for metric units and in mph for imperial units            | Number | -
 //! `wind_kmh{,_{favg,fmin,fmax,ffin}}`          | Wind speed. The wind speed in km/h                                            | Number | -
+//! `wind_mph{,_{favg,fmin,fmax,ffin}}`          | Wind speed. The wind speed in mph                                             | Number | -
 //! `direction{,_{favg,fmin,fmax,ffin}}`         | Wind direction, e.g. "NE"                                                     | Text   | -
+//! `wind_dir{,_{favg,fmin,fmax,ffin}}`          | Wind direction on a 16-point compass, e.g. "NNE"                              | Text   | -
+//! `wind_arrow{,_{favg,fmin,fmax,ffin}}`        | Wind direction as one of "↑↗→↘↓↙←↖", pointing where the wind comes from. `wind_calm_symbol` if there is no direction | Text   | -
+//! `wind_gust{,_{favg,fmin,fmax,ffin}}`         | Wind gust speed, if reported (NWS only)                                       | Number | -
+//! `wind_gust_kmh{,_{favg,fmin,fmax,ffin}}`     | Wind gust speed in km/h, if reported (NWS only)                               | Number | -
+//! `precip_prob{,_{favg,fmin,fmax,ffin}}`       | Probability of precipitation (NWS only)                                       | Number | %
+//! `alert`                                      | Event name of the most severe active alert, empty if there are none (NWS only) | Text   | -
+//! `alert_headline`                             | Headline of the most severe active alert, empty if there are none (NWS only)   | Text   | -
+//! `alert_count`                                | Number of active alerts (NWS only)                                            | Number | -
+//! `updated`                                    | How long ago the forecast was updated, e.g. "12m ago". Empty if unavailable (NWS only) | Text   | -
+//! `updated_at`                                 | When the forecast was updated. Empty if unavailable (NWS only)                | Datetime | -
+//! `sunrise`                                    | Time of sunrise, computed from the coordinates. Empty if the sun does not rise or set today, or the coordinates are unknown | Datetime | -
+//! `sunset`                                     | Time of sunset, computed from the coordinates. Empty if the sun does not rise or set today, or the coordinates are unknown | Datetime | -
+//! `is_daylight`                                | Present if the sun is up                                                      | Flag   | -
+//! `moon_phase`                                 | Phase of the moon today, e.g. "Waxing Crescent"                               | Text   | -
+//! `moon_icon`                                  | Symbol of the phase of the moon today, e.g. "🌒"                              | Text   | -
+//! `is_precip`                                  | Present if it is raining, snowing, sleeting or thundering                     | Flag   | -
+//! `is_clear`                                   | Present if the sky is clear                                                   | Flag   | -
+//! `is_stale`                                   | Present if the forecast is older than `stale_after`, or if the last update failed | Flag   | -
+//! `is_freezing`                                | Present if the temperature is below `freeze_below`                            | Flag   | -
+//! `is_hot`                                     | Present if the temperature is above `heat_above`                              | Flag   | -
+//! `is_gusty`                                   | Present if the wind gusts are above `gust_warn_above`                         | Flag   | -
+//! `is_calm`                                    | Present if the wind has no direction, e.g. when it is below the `calm_below` of `nws`, so that `{$is_calm calm\|$wind}` shows "calm" | Flag | -
+//! `provider`                                   | Name of the weather service that provided the data, e.g. `nws`                | Text   | -
+//! `debug_stats`                                | Requests made by the weather service so far, how many failed and how many locations came from the cache. Only available with `nws`. | Text | -
 //!
 //! You can use the suffixes noted above to get the following:
 //!
@@ -91,11 +229,24 @@
 //! `_favg`   | Average forecast value
 //! `_fmin`   | Minimum forecast value
 //! `_fmax`   | Maximum forecast value
-//! `_ffin`   | Final forecast value
+//! `_ffin`   | Final forecast value. By default the one of the period at `forecast_hours`, right after the aggregated ones. With the `final_period` option of NWS and Open-Meteo it can be the last aggregated period instead, or left out.
+//!
+//! The NWS service also provides each upcoming forecast period within `forecast_hours`, numbered
+//! from 1, through the `icon`, `weather`, `temp`, `apparent`, `humidity`, `wind`, `wind_kmh`,
+//! `wind_mph`, `direction`, `wind_dir`, `wind_arrow` and `precip_prob` keys with a `_p<N>` suffix, e.g.
+//! `$temp_p1`. The start of each period is available as `time_p<N>`, so `{$time_p1.datetime(f:'%-I%P') $temp_p1|}` renders
+//! as "3pm 18°".
+//!
+//! The `icon_strip` key shows the weather of the upcoming periods in a compact row, e.g. "☀☁🌧".
+//! Since a key holds a single icon, the strip uses Unicode symbols instead of the icon set; use
+//! `$icon_p1 $icon_p2 ...` for themed icons.
 //!
 //! Action          | Description                               | Default button
 //! ----------------|-------------------------------------------|---------------
-//! `toggle_format` | Toggles between `format` and `format_alt` | Left
+//! `toggle_format` | Toggles between `format` and `format_alt` | Left, if `format_alt` is set
+//! `cycle_location` | Switches to the next location (NWS only) | Left, if `format_alt` is not set and there are several locations
+//! `cycle_view`    | Switches to the next of `format` and `views` | Middle
+//! `refresh`       | Updates the weather now, at most once per `refresh_cooldown` | Right
 //!
 //! # Example
 //!
@@ -104,7 +255,7 @@
 //! ```toml
 //! [[block]]
 //! block = "weather"
-//! format = " $icon $weather ($location) $temp, $wind m/s $direction "
+//! format = " $icon $weather ($location) $temp, $wind km/h $direction "
 //! format_alt = " $icon_ffin Forecast (9 hour avg) {$temp_favg ({$temp_fmin}-{$temp_fmax})|Unavailable} "
 //! [block.service]
 //! name = "openweathermap"
@@ -114,54 +265,283 @@
 //! forecast_hours = 9
 //! ```
 //!
+//! Use the NWS, and Open-Meteo whenever the NWS is unavailable:
+//!
+//! ```toml
+//! [[block]]
+//! block = "weather"
+//! format = " $icon $temp ($provider) "
+//! [block.service]
+//! name = "nws"
+//! coordinates = ["39.7456", "-97.0892"]
+//! [[block.fallback]]
+//! name = "openmeteo"
+//! coordinates = ["39.7456", "-97.0892"]
+//! ```
+//!
+//! # Checking the Configuration
+//!
+//! Running `i3status-rs --check-weather config.toml` fetches the weather once from each service of
+//! every weather block, printing the location, the values and any errors instead of starting the
+//! bar. The exit status is non-zero if any service failed.
+//!
 //! # Used Icons
 //!
 //! - `weather_sun` (when weather is reported as "Clear" during the day)
 //! - `weather_moon` (when weather is reported as "Clear" at night)
 //! - `weather_clouds` (when weather is reported as "Clouds" during the day)
 //! - `weather_clouds_night` (when weather is reported as "Clouds" at night)
+//! - `weather_partly_cloudy` (when weather is reported as "Partly Cloudy" during the day)
+//! - `weather_partly_cloudy_night` (when weather is reported as "Partly Cloudy" at night)
 //! - `weather_fog` (when weather is reported as "Fog" or "Mist" during the day)
 //! - `weather_fog_night` (when weather is reported as "Fog" or "Mist" at night)
 //! - `weather_rain` (when weather is reported as "Rain" or "Drizzle" during the day)
 //! - `weather_rain_night` (when weather is reported as "Rain" or "Drizzle" at night)
-//! - `weather_snow` (when weather is reported as "Snow")
+//! - `weather_sleet` (when weather is reported as "Sleet", "Hail" or "Freezing Rain" during the day)
+//! - `weather_sleet_night` (when weather is reported as "Sleet", "Hail" or "Freezing Rain" at night)
+//! - `weather_snow` (when weather is reported as "Snow" during the day)
+//! - `weather_snow_night` (when weather is reported as "Snow" at night)
 //! - `weather_thunder` (when weather is reported as "Thunderstorm" during the day)
 //! - `weather_thunder_night` (when weather is reported as "Thunderstorm" at night)
+//!
+//! The icons of the weather block alone can be replaced with the `icons` option, keyed by the
+//! names of the weather conditions: "Clear", "Clouds", "Partly Cloudy", "Fog", "Rain", "Sleet",
+//! "Snow", "Thunder" and "Unknown". The same text is used by day and by night, and conditions left
+//! out use the icon of the theme.
+//!
+//! ```toml
+//! [[block]]
+//! block = "weather"
+//! [block.icons]
+//! Snow = "❄"
+//! Thunder = "⚡"
+//! [block.service]
+//! name = "openmeteo"
+//! coordinates = ["39.7456", "-97.0892"]
+//! ```
 
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use std::fmt;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::formatting::Format;
 
 use super::prelude::*;
 
+make_log_macro!(debug, "weather");
+make_log_macro!(info, "weather");
+make_log_macro!(warn, "weather");
+
 pub mod met_no;
 pub mod nws;
+pub mod open_meteo;
 pub mod open_weather_map;
 
 const IP_API_URL: &str = "https://ipapi.co/json";
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1/search";
+
+const DEFAULT_FORMAT: &str = " $icon $weather $temp ";
+/// Default format with `format_icon`, for minimal bars
+const FORMAT_ICON: &str = " $icon $temp ";
+
+const MPH_TO_KPH: f64 = 1.609344;
+const HPA_TO_INHG: f64 = 0.029529983;
+const HPA_TO_MMHG: f64 = 0.750061683;
 
 static LAST_AUTOLOCATE: Mutex<Option<AutolocateResult>> = Mutex::new(None);
 
+/// The block's configuration, boxed so that its many options don't grow every `BlockConfig`
+#[derive(Deserialize, Debug)]
+#[serde(transparent)]
+pub struct Config(Box<Settings>);
+
+impl std::ops::Deref for Config {
+    type Target = Settings;
+
+    fn deref(&self) -> &Settings {
+        &self.0
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
-pub struct Config {
+pub struct Settings {
     #[serde(default = "default_interval")]
     pub interval: Seconds,
+    #[serde(default = "default_jitter")]
+    pub jitter: Seconds<false>,
+    #[serde(default = "default_refresh_cooldown")]
+    pub refresh_cooldown: Seconds<false>,
     #[serde(default)]
     pub format: FormatConfig,
+    #[serde(default)]
+    pub format_icon: bool,
     pub format_alt: Option<FormatConfig>,
+    #[serde(default)]
+    pub views: Vec<FormatConfig>,
+    #[serde(default, deserialize_with = "deserialize_icons")]
+    pub icons: HashMap<String, String>,
     pub service: WeatherService,
     #[serde(default)]
+    pub fallback: Vec<WeatherService>,
+    #[serde(default = "default_provider_timeout")]
+    pub provider_timeout: Seconds,
+    pub proxy: Option<String>,
+    #[serde(default)]
     pub autolocate: bool,
     pub autolocate_interval: Option<Seconds>,
+    pub city: Option<String>,
+    #[serde(default = "default_max_failures")]
+    pub max_failures: usize,
+    pub stale_after: Option<Seconds>,
+    pub location_name: Option<String>,
+    pub temp_precision: Option<usize>,
+    pub wind_precision: Option<usize>,
+    #[serde(default)]
+    pub wind_calm_symbol: String,
+    pub max_verbose_len: Option<usize>,
+    #[serde(default)]
+    pub humidity_levels: HumidityLevels,
+    #[serde(default)]
+    pub comfort_levels: ComfortLevels,
+    pub freeze_below: Option<f64>,
+    pub heat_above: Option<f64>,
+    pub gust_warn_above: Option<f64>,
+    #[serde(default)]
+    pub temp_color: bool,
+    #[serde(default)]
+    pub warn_on: Vec<Condition>,
+    #[serde(default)]
+    pub critical_on: Vec<Condition>,
+    #[serde(default = "default_icon_strip_len")]
+    pub icon_strip_len: usize,
+    pub trend_horizon: Option<usize>,
+    #[serde(default = "default_trend_threshold")]
+    pub trend_threshold: f64,
+}
+
+/// Relative humidity thresholds, in %, for the `humidity_level` key
+#[derive(Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields, default)]
+pub struct HumidityLevels {
+    #[default(30.0)]
+    pub dry_below: f64,
+    #[default(60.0)]
+    pub humid_above: f64,
+    #[default(80.0)]
+    pub oppressive_above: f64,
+}
+
+impl HumidityLevels {
+    fn level(&self, humidity: f64) -> &'static str {
+        if humidity < self.dry_below {
+            "dry"
+        } else if humidity > self.oppressive_above {
+            "oppressive"
+        } else if humidity > self.humid_above {
+            "humid"
+        } else {
+            "comfortable"
+        }
+    }
+}
+
+/// Apparent temperature thresholds for the `comfort` key
+#[derive(Deserialize, Debug, SmartDefault)]
+#[serde(deny_unknown_fields, default)]
+pub struct ComfortLevels {
+    /// Units the thresholds are given in
+    units: UnitSystem,
+    #[default(0.0)]
+    pub freezing_below: f64,
+    #[default(10.0)]
+    pub cold_below: f64,
+    #[default(18.0)]
+    pub cool_below: f64,
+    #[default(24.0)]
+    pub warm_above: f64,
+    #[default(30.0)]
+    pub hot_above: f64,
+}
+
+impl ComfortLevels {
+    /// Classify an apparent temperature, given in °C
+    fn level(&self, celsius: f64) -> &'static str {
+        let temp = self.units.temp_unit().convert_celsius(celsius);
+        if temp < self.freezing_below {
+            "freezing"
+        } else if temp < self.cold_below {
+            "cold"
+        } else if temp < self.cool_below {
+            "cool"
+        } else if temp > self.hot_above {
+            "hot"
+        } else if temp > self.warm_above {
+            "warm"
+        } else {
+            "mild"
+        }
+    }
+
+    /// The state showing a temperature, given in °C, from cold (info) to hot (critical)
+    fn state(&self, celsius: f64) -> State {
+        match self.level(celsius) {
+            "freezing" | "cold" => State::Info,
+            "warm" => State::Warning,
+            "hot" => State::Critical,
+            _ => State::Good,
+        }
+    }
+}
+
+/// Make sure the `icons` are keyed by known weather conditions
+fn deserialize_icons<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let icons = HashMap::<String, String>::deserialize(deserializer)?;
+    let words: Vec<_> = WeatherIcon::KINDS.iter().map(|icon| icon.to_word()).collect();
+    match icons.keys().find(|word| !words.contains(&word.as_str())) {
+        Some(word) => Err(serde::de::Error::custom(format!(
+            "unknown weather condition '{word}' in 'icons', expected one of {}",
+            words.join(", ")
+        ))),
+        None => Ok(icons),
+    }
 }
 
 fn default_interval() -> Seconds {
     Seconds::new(600)
 }
 
+fn default_trend_threshold() -> f64 {
+    1.0
+}
+
+fn default_icon_strip_len() -> usize {
+    6
+}
+
+fn default_max_failures() -> usize {
+    3
+}
+
+fn default_refresh_cooldown() -> Seconds<false> {
+    Seconds::new(30)
+}
+
+fn default_jitter() -> Seconds<false> {
+    Seconds::new(5)
+}
+
+fn default_provider_timeout() -> Seconds {
+    Seconds::new(60)
+}
+
 #[async_trait]
 trait WeatherProvider {
     async fn get_weather(
@@ -169,6 +549,23 @@ trait WeatherProvider {
         autolocated_location: Option<&Coordinates>,
         need_forecast: bool,
     ) -> Result<WeatherResult>;
+
+    /// Whether the provider rotates through several locations
+    fn has_several_locations(&self) -> bool {
+        false
+    }
+
+    /// Switch to the next location, if the provider rotates through several. Returns whether
+    /// the location changed.
+    fn cycle_location(&self) -> bool {
+        false
+    }
+
+    /// Whether the provider retries its failed requests itself, knowing which errors are worth
+    /// retrying. The others are retried as a whole.
+    fn retries_requests(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -176,37 +573,204 @@ trait WeatherProvider {
 pub enum WeatherService {
     OpenWeatherMap(open_weather_map::Config),
     MetNo(met_no::Config),
-    Nws(nws::Config),
+    Nws(Box<nws::Config>),
+    OpenMeteo(open_meteo::Config),
+}
+
+impl Settings {
+    /// Whether the services are given the location by the block, with `autolocate` or `city`,
+    /// instead of using their own
+    fn block_located(&self) -> Result<bool> {
+        if self.city.is_none() {
+            return Ok(self.autolocate);
+        }
+        if self.autolocate {
+            return Err(Error::new("city cannot be combined with autolocate"));
+        }
+        let mut services = std::iter::once(&self.service).chain(&self.fallback);
+        if services.any(WeatherService::has_coordinates) {
+            return Err(Error::new("city and coordinates cannot both be set"));
+        }
+        Ok(true)
+    }
+}
+
+impl WeatherService {
+    fn has_coordinates(&self) -> bool {
+        match self {
+            Self::OpenWeatherMap(config) => config.has_coordinates(),
+            Self::MetNo(config) => config.has_coordinates(),
+            Self::Nws(config) => config.has_coordinates(),
+            Self::OpenMeteo(config) => config.has_coordinates(),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::OpenWeatherMap(_) => "openweathermap",
+            Self::MetNo(_) => "metno",
+            Self::Nws(_) => "nws",
+            Self::OpenMeteo(_) => "openmeteo",
+        }
+    }
+
+    async fn provider(
+        &self,
+        autolocate: bool,
+        client: reqwest::Client,
+    ) -> Result<Box<dyn WeatherProvider + Send + Sync + '_>> {
+        Ok(match self {
+            Self::MetNo(config) => Box::new(met_no::Service::new(config, client)?),
+            Self::OpenWeatherMap(config) => {
+                Box::new(open_weather_map::Service::new(autolocate, config, client).await?)
+            }
+            Self::Nws(config) => Box::new(nws::Service::new(autolocate, config, client).await?),
+            Self::OpenMeteo(config) => Box::new(open_meteo::Service::new(config, client)),
+        })
+    }
+}
+
+/// The HTTP client used by the block: the shared one, or a dedicated one going through `proxy`
+fn http_client(proxy: Option<&str>) -> Result<reqwest::Client> {
+    let Some(proxy) = proxy else {
+        return Ok(REQWEST_CLIENT.clone());
+    };
+    reqwest::Client::builder()
+        .user_agent(crate::APP_USER_AGENT)
+        .timeout(crate::REQWEST_TIMEOUT)
+        .proxy(reqwest::Proxy::all(proxy).or_error(|| format!("Invalid proxy '{proxy}'"))?)
+        .build()
+        .error("Failed to build the HTTP client")
 }
 
 #[derive(Clone, Copy)]
 enum WeatherIcon {
     Clear { is_night: bool },
     Clouds { is_night: bool },
+    PartlyCloudy { is_night: bool },
     Fog { is_night: bool },
     Rain { is_night: bool },
-    Snow,
+    Sleet { is_night: bool },
+    Snow { is_night: bool },
     Thunder { is_night: bool },
     Default,
 }
 
 impl WeatherIcon {
+    /// One of each kind of weather
+    const KINDS: [Self; 9] = [
+        Self::Clear { is_night: false },
+        Self::Clouds { is_night: false },
+        Self::PartlyCloudy { is_night: false },
+        Self::Fog { is_night: false },
+        Self::Rain { is_night: false },
+        Self::Sleet { is_night: false },
+        Self::Snow { is_night: false },
+        Self::Thunder { is_night: false },
+        Self::Default,
+    ];
+
+    /// The icon of the theme, unless `overrides` has a replacement for this kind of weather
+    fn to_value(self, overrides: &HashMap<String, String>) -> Value {
+        match overrides.get(self.to_word()) {
+            Some(text) => Value::text(text.clone()),
+            None => Value::icon(self.to_icon_str()),
+        }
+    }
+
     fn to_icon_str(self) -> &'static str {
         match self {
             Self::Clear { is_night: false } => "weather_sun",
             Self::Clear { is_night: true } => "weather_moon",
             Self::Clouds { is_night: false } => "weather_clouds",
             Self::Clouds { is_night: true } => "weather_clouds_night",
+            Self::PartlyCloudy { is_night: false } => "weather_partly_cloudy",
+            Self::PartlyCloudy { is_night: true } => "weather_partly_cloudy_night",
             Self::Fog { is_night: false } => "weather_fog",
             Self::Fog { is_night: true } => "weather_fog_night",
             Self::Rain { is_night: false } => "weather_rain",
             Self::Rain { is_night: true } => "weather_rain_night",
-            Self::Snow => "weather_snow",
+            Self::Sleet { is_night: false } => "weather_sleet",
+            Self::Sleet { is_night: true } => "weather_sleet_night",
+            Self::Snow { is_night: false } => "weather_snow",
+            Self::Snow { is_night: true } => "weather_snow_night",
             Self::Thunder { is_night: false } => "weather_thunder",
             Self::Thunder { is_night: true } => "weather_thunder_night",
             Self::Default => "weather_default",
         }
     }
+
+    /// The kind of weather, regardless of the time of day
+    fn condition(self) -> Option<Condition> {
+        match self {
+            Self::Clear { .. } => Some(Condition::Clear),
+            Self::Clouds { .. } => Some(Condition::Clouds),
+            Self::PartlyCloudy { .. } => Some(Condition::PartlyCloudy),
+            Self::Fog { .. } => Some(Condition::Fog),
+            Self::Rain { .. } => Some(Condition::Rain),
+            Self::Sleet { .. } => Some(Condition::Sleet),
+            Self::Snow { .. } => Some(Condition::Snow),
+            Self::Thunder { .. } => Some(Condition::Thunder),
+            Self::Default => None,
+        }
+    }
+
+    /// Whether anything is falling from the sky
+    fn is_precip(self) -> bool {
+        matches!(
+            self,
+            Self::Rain { .. } | Self::Sleet { .. } | Self::Snow { .. } | Self::Thunder { .. }
+        )
+    }
+
+    fn is_clear(self) -> bool {
+        matches!(self, Self::Clear { .. })
+    }
+
+    /// A single Unicode symbol for the weather, for places where the icon set can't be used
+    fn to_symbol(self) -> &'static str {
+        match self {
+            Self::Clear { is_night: false } => "☀",
+            Self::Clear { is_night: true } => "☾",
+            Self::Clouds { .. } => "☁",
+            Self::PartlyCloudy { .. } => "⛅",
+            Self::Fog { .. } => "🌫",
+            Self::Rain { .. } => "🌧",
+            Self::Sleet { .. } => "🌨",
+            Self::Snow { .. } => "❄",
+            Self::Thunder { .. } => "⛈",
+            Self::Default => "?",
+        }
+    }
+
+    /// A brief description of the weather
+    fn to_word(self) -> &'static str {
+        match self {
+            Self::Clear { .. } => "Clear",
+            Self::Clouds { .. } => "Clouds",
+            Self::PartlyCloudy { .. } => "Partly Cloudy",
+            Self::Fog { .. } => "Fog",
+            Self::Thunder { .. } => "Thunder",
+            Self::Rain { .. } => "Rain",
+            Self::Sleet { .. } => "Sleet",
+            Self::Snow { .. } => "Snow",
+            Self::Default => "Unknown",
+        }
+    }
+}
+
+/// Weather conditions that can change the state of the block, see `warn_on` and `critical_on`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    Clear,
+    Clouds,
+    PartlyCloudy,
+    Fog,
+    Rain,
+    Sleet,
+    Snow,
+    Thunder,
 }
 
 #[derive(Debug)]
@@ -226,81 +790,441 @@ impl PartialEq for Wind {
     }
 }
 
+#[derive(Clone)]
 struct WeatherMoment {
     icon: WeatherIcon,
     weather: String,
     weather_verbose: String,
+    /// Detailed forecast text, if reported
+    weather_detailed: Option<String>,
     temp: f64,
     apparent: f64,
     humidity: f64,
+    dewpoint: Option<f64>,
+    /// Barometric pressure in the unit of `WeatherResult::pressure_unit`
+    pressure: Option<f64>,
+    /// Visibility in km or miles, depending on the unit system
+    visibility: Option<f64>,
+    /// Wind speed in km/h or mph, depending on the unit system
     wind: f64,
     wind_kmh: f64,
+    wind_mph: f64,
     wind_direction: Option<f64>,
+    wind_gust: Option<f64>,
+    wind_gust_kmh: Option<f64>,
+    precip_prob: Option<f64>,
 }
+
+#[derive(Clone)]
 struct ForecastAggregate {
     temp: f64,
     apparent: f64,
     humidity: f64,
+    dewpoint: Option<f64>,
+    /// Barometric pressure in the unit of `WeatherResult::pressure_unit`
+    pressure: Option<f64>,
+    /// Visibility in km or miles, depending on the unit system
+    visibility: Option<f64>,
+    /// Wind speed in km/h or mph, depending on the unit system
     wind: f64,
     wind_kmh: f64,
+    wind_mph: f64,
     wind_direction: Option<f64>,
+    wind_gust: Option<f64>,
+    wind_gust_kmh: Option<f64>,
+    precip_prob: Option<f64>,
+    /// Start of the period the values are from, for the minimum and maximum temperature
+    start: Option<DateTime<Utc>>,
 }
 
+#[derive(Clone)]
 struct WeatherResult {
     location: String,
+    /// Distance from the named location, in km or miles
+    location_distance: Option<f64>,
+    /// Bearing from the named location, in degrees
+    location_bearing: Option<f64>,
     current_weather: WeatherMoment,
     forecast: Option<Forecast>,
+    alerts: Option<WeatherAlerts>,
+    /// When the provider last updated the forecast
+    updated: Option<DateTime<Utc>>,
+    /// Individual upcoming forecast periods, exposed with the `_p<N>` suffixes
+    periods: Vec<ForecastPeriod>,
+    /// Latitude and longitude of the forecast, if known
+    coordinates: Option<(f64, f64)>,
+    /// Unit the temperatures are expressed in
+    temp_unit: TempUnit,
+    /// Unit the pressures are expressed in
+    pressure_unit: PressureUnit,
+    /// Unit system of the wind speeds and distances
+    units: UnitSystem,
+    /// Name of the weather service the data comes from, as in the `name` of its configuration
+    provider: &'static str,
+    /// Counters of the requests made by the weather service, if it keeps any
+    debug_stats: Option<String>,
+    /// Highs and lows of today and tomorrow, if the weather service was asked for them
+    daily: Option<DailySummary>,
+    /// The NWS forecast grid point of the location
+    grid_point: Option<GridPoint>,
+}
+
+/// A point of the NWS forecast grid, as used by the `office`, `grid_x` and `grid_y` options
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct GridPoint {
+    office: String,
+    x: u32,
+    y: u32,
 }
 
+#[derive(Clone)]
+struct DailySummary {
+    today_high: Option<f64>,
+    today_low: Option<f64>,
+    tomorrow_high: Option<f64>,
+    tomorrow_low: Option<f64>,
+}
+
+#[derive(Clone)]
+struct ForecastPeriod {
+    start: Option<DateTime<Utc>>,
+    weather: WeatherMoment,
+}
+
+#[derive(Clone)]
+struct WeatherAlerts {
+    count: usize,
+    /// Event name of the most severe alert
+    event: Option<String>,
+    /// Headline of the most severe alert
+    headline: Option<String>,
+}
+
+#[derive(Clone)]
 struct Forecast {
     avg: ForecastAggregate,
     min: ForecastAggregate,
     max: ForecastAggregate,
-    fin: WeatherMoment,
+    /// The final forecast value, see `FinalPeriod`
+    fin: Option<WeatherMoment>,
+    /// Number of periods the aggregates are computed from
+    count: usize,
+    /// Probability that it rains in at least one of the periods, in %
+    precip_any: Option<f64>,
+}
+
+/// Round `value` to `precision` decimal places, if set
+fn round_to(value: &mut f64, precision: Option<usize>) {
+    if let Some(precision) = precision {
+        let factor = 10f64.powi(precision as i32);
+        *value = (*value * factor).round() / factor;
+    }
+}
+
+macro_rules! impl_round {
+    ($($ty:ty),*) => {
+        $(
+            impl $ty {
+                fn round(&mut self, temp: Option<usize>, wind: Option<usize>) {
+                    round_to(&mut self.temp, temp);
+                    round_to(&mut self.apparent, temp);
+                    if let Some(dewpoint) = &mut self.dewpoint {
+                        round_to(dewpoint, temp);
+                    }
+                    round_to(&mut self.wind, wind);
+                    round_to(&mut self.wind_kmh, wind);
+                    round_to(&mut self.wind_mph, wind);
+                    if let Some(wind_gust) = &mut self.wind_gust {
+                        round_to(wind_gust, wind);
+                    }
+                    if let Some(wind_gust_kmh) = &mut self.wind_gust_kmh {
+                        round_to(wind_gust_kmh, wind);
+                    }
+                }
+            }
+        )*
+    };
 }
+impl_round!(WeatherMoment, ForecastAggregate);
 
 impl WeatherResult {
-    fn into_values(self) -> Values {
+    /// Round temperatures and wind speeds to the given number of decimal places
+    fn round(&mut self, temp: Option<usize>, wind: Option<usize>) {
+        self.current_weather.round(temp, wind);
+        if let Some(forecast) = &mut self.forecast {
+            forecast.avg.round(temp, wind);
+            forecast.min.round(temp, wind);
+            forecast.max.round(temp, wind);
+            if let Some(fin) = &mut forecast.fin {
+                fin.round(temp, wind);
+            }
+        }
+        for period in &mut self.periods {
+            period.weather.round(temp, wind);
+        }
+        if let Some(daily) = &mut self.daily {
+            let extremes = [
+                &mut daily.today_high,
+                &mut daily.today_low,
+                &mut daily.tomorrow_high,
+                &mut daily.tomorrow_low,
+            ];
+            for value in extremes.into_iter().flatten() {
+                round_to(value, temp);
+            }
+        }
+    }
+
+    /// Compare the current temperature with the one `horizon` periods ahead, or with the final
+    /// forecast value
+    fn temp_trend(&self, horizon: Option<usize>, threshold: f64) -> Option<&'static str> {
+        let later = match horizon {
+            Some(horizon) if horizon > 0 && !self.periods.is_empty() => {
+                self.periods[horizon.min(self.periods.len()) - 1].weather.temp
+            }
+            _ => self.forecast.as_ref()?.fin.as_ref()?.temp,
+        };
+        let change = later - self.current_weather.temp;
+        Some(if change >= threshold {
+            "↑"
+        } else if change <= -threshold {
+            "↓"
+        } else {
+            "→"
+        })
+    }
+
+    /// The strongest of the current and the forecast wind gusts, in km/h or mph
+    fn max_gust(&self) -> Option<f64> {
+        let forecast = self
+            .forecast
+            .as_ref()
+            .and_then(|forecast| forecast.max.wind_gust);
+        match (self.current_weather.wind_gust, forecast) {
+            (Some(current), Some(forecast)) => Some(current.max(forecast)),
+            (current, forecast) => current.or(forecast),
+        }
+    }
+
+    /// How the current weather feels. The apparent temperature is used, lowered to the wind chill
+    /// when it is cold and windy, whichever formula the provider computes it with.
+    fn comfort(&self, levels: &ComfortLevels) -> &'static str {
+        let now = &self.current_weather;
+        let temp = self.temp_unit.to_celsius(now.temp);
+        let mut apparent = self.temp_unit.to_celsius(now.apparent);
+        if temp <= 10.0 && now.wind_kmh > 4.8 {
+            apparent = apparent.min(wind_chill(temp, now.wind_kmh));
+        }
+        levels.level(apparent)
+    }
+
+    /// The symbols of the first `max_len` upcoming periods
+    fn icon_strip(&self, max_len: usize) -> String {
+        self.periods
+            .iter()
+            .take(max_len)
+            .map(|period| period.weather.icon.to_symbol())
+            .collect()
+    }
+
+    /// Truncate the verbose and detailed descriptions to `max_len` grapheme clusters
+    fn truncate_verbose(&mut self, max_len: usize) {
+        let fin = self
+            .forecast
+            .as_mut()
+            .and_then(|forecast| forecast.fin.as_mut());
+        let moments = std::iter::once(&mut self.current_weather).chain(fin);
+        for moment in moments {
+            truncate(&mut moment.weather_verbose, max_len);
+            if let Some(detailed) = &mut moment.weather_detailed {
+                truncate(detailed, max_len);
+            }
+        }
+    }
+
+    /// Whether the forecast was updated longer ago than `stale_after`. Always `false` if the
+    /// provider doesn't report an update time.
+    fn is_stale(&self, stale_after: Duration) -> bool {
+        self.updated.is_some_and(|updated| {
+            (Utc::now() - updated)
+                .to_std()
+                .is_ok_and(|age| age > stale_after)
+        })
+    }
+
+    fn into_values(self, config: &Config) -> Values {
+        let icons = &config.icons;
+        let arrow = |direction| {
+            Value::text(wind_arrow(direction).unwrap_or(&config.wind_calm_symbol).into())
+        };
+        // The degree sign doesn't belong to kelvins
+        let temp_unit = self.temp_unit;
+        let degrees = move |temp: f64| match temp_unit {
+            TempUnit::Kelvin => Value::number(temp),
+            _ => Value::degrees(temp),
+        };
         let mut values = map! {
             "location" => Value::text(self.location),
+            "provider" => Value::text(self.provider.into()),
+            [if let Some(stats) = self.debug_stats] "debug_stats" => Value::text(stats),
+            [if let Some(grid_point) = &self.grid_point] "office" => Value::text(grid_point.office.clone()),
+            [if let Some(grid_point) = &self.grid_point] "grid" => Value::text(format!("{},{}", grid_point.x, grid_point.y)),
+            "temp_unit" => Value::text(self.temp_unit.label().into()),
+            "pressure_unit" => Value::text(self.pressure_unit.label().into()),
+            "wind_unit" => Value::text(self.units.speed_label().into()),
+            "distance_unit" => Value::text(self.units.distance_label().into()),
+            "humidity_unit" => Value::text("%".into()),
+            [if let Some(distance) = self.location_distance] "location_distance" => Value::number(distance),
+            [if let Some(bearing) = self.location_bearing] "location_bearing" => Value::text(convert_wind_direction(Some(bearing)).into()),
             //current_weather
-            "icon" => Value::icon(self.current_weather.icon.to_icon_str()),
-            "temp" => Value::degrees(self.current_weather.temp),
-            "apparent" => Value::degrees(self.current_weather.apparent),
+            "icon" => self.current_weather.icon.to_value(icons),
+            "temp" => degrees(self.current_weather.temp),
+            "apparent" => degrees(self.current_weather.apparent),
             "humidity" => Value::percents(self.current_weather.humidity),
             "weather" => Value::text(self.current_weather.weather),
             "weather_verbose" => Value::text(self.current_weather.weather_verbose),
+            "weather_detailed" => Value::text(
+                self.current_weather.weather_detailed.unwrap_or_default()
+            ),
             "wind" => Value::number(self.current_weather.wind),
             "wind_kmh" => Value::number(self.current_weather.wind_kmh),
+            "wind_mph" => Value::number(self.current_weather.wind_mph),
             "direction" => Value::text(convert_wind_direction(self.current_weather.wind_direction).into()),
+            "wind_dir" => Value::text(compass_direction(self.current_weather.wind_direction).into()),
+            "wind_arrow" => arrow(self.current_weather.wind_direction),
+            [if let Some(dewpoint) = self.current_weather.dewpoint] "dewpoint" => degrees(dewpoint),
+            [if let Some(pressure) = self.current_weather.pressure] "pressure" => Value::number(pressure),
+            [if let Some(visibility) = self.current_weather.visibility] "visibility" => Value::number(visibility),
+            [if let Some(wind_gust) = self.current_weather.wind_gust] "wind_gust" => Value::number(wind_gust),
+            [if let Some(wind_gust_kmh) = self.current_weather.wind_gust_kmh] "wind_gust_kmh" => Value::number(wind_gust_kmh),
+            [if let Some(precip_prob) = self.current_weather.precip_prob] "precip_prob" => Value::percents(precip_prob),
+            [if self.current_weather.icon.is_precip()] "is_precip" => Value::flag(),
+            [if self.current_weather.icon.is_clear()] "is_clear" => Value::flag(),
+            [if self.current_weather.wind_direction.is_none()] "is_calm" => Value::flag(),
         };
 
+        if let Some(daily) = self.daily {
+            map! { @extend values
+                [if let Some(temp) = daily.today_high] "today_high" => degrees(temp),
+                [if let Some(temp) = daily.today_low] "today_low" => degrees(temp),
+                [if let Some(temp) = daily.tomorrow_high] "tomorrow_high" => degrees(temp),
+                [if let Some(temp) = daily.tomorrow_low] "tomorrow_low" => degrees(temp),
+            }
+        }
+
         if let Some(forecast) = self.forecast {
             macro_rules! map_forecasts {
                 ({$($suffix: literal => $src: expr),* $(,)?}) => {
                     map!{ @extend values
                         $(
-                            concat!("temp_f", $suffix) => Value::degrees($src.temp),
-                            concat!("apparent_f", $suffix) => Value::degrees($src.apparent),
+                            concat!("temp_f", $suffix) => degrees($src.temp),
+                            concat!("apparent_f", $suffix) => degrees($src.apparent),
                             concat!("humidity_f", $suffix) => Value::percents($src.humidity),
                             concat!("wind_f", $suffix) => Value::number($src.wind),
                             concat!("wind_kmh_f", $suffix) => Value::number($src.wind_kmh),
+                            concat!("wind_mph_f", $suffix) => Value::number($src.wind_mph),
                             concat!("direction_f", $suffix) => Value::text(convert_wind_direction($src.wind_direction).into()),
+                            concat!("wind_dir_f", $suffix) => Value::text(compass_direction($src.wind_direction).into()),
+                            concat!("wind_arrow_f", $suffix) => arrow($src.wind_direction),
                         )*
                     }
+                    $(
+                        if let Some(dewpoint) = $src.dewpoint {
+                            values.insert(concat!("dewpoint_f", $suffix).into(), degrees(dewpoint));
+                        }
+                        if let Some(pressure) = $src.pressure {
+                            values.insert(concat!("pressure_f", $suffix).into(), Value::number(pressure));
+                        }
+                        if let Some(visibility) = $src.visibility {
+                            values.insert(concat!("visibility_f", $suffix).into(), Value::number(visibility));
+                        }
+                        if let Some(wind_gust) = $src.wind_gust {
+                            values.insert(concat!("wind_gust_f", $suffix).into(), Value::number(wind_gust));
+                        }
+                        if let Some(wind_gust_kmh) = $src.wind_gust_kmh {
+                            values.insert(concat!("wind_gust_kmh_f", $suffix).into(), Value::number(wind_gust_kmh));
+                        }
+                        if let Some(precip_prob) = $src.precip_prob {
+                            values.insert(concat!("precip_prob_f", $suffix).into(), Value::percents(precip_prob));
+                        }
+                    )*
                 };
             }
             map_forecasts!({
                 "avg" => forecast.avg,
                 "min" => forecast.min,
                 "max" => forecast.max,
-                "fin" => forecast.fin,
             });
+            if let Some(fin) = &forecast.fin {
+                map_forecasts!({ "fin" => fin });
+                map! { @extend values
+                    "icon_ffin" => fin.icon.to_value(icons),
+                    "weather_ffin" => Value::text(fin.weather.clone()),
+                    "weather_verbose_ffin" => Value::text(fin.weather_verbose.clone()),
+                    "weather_detailed_ffin" => Value::text(
+                        fin.weather_detailed.clone().unwrap_or_default()
+                    ),
+                }
+            }
+
+            for (key, start) in [
+                ("temp_min_at", forecast.min.start),
+                ("temp_max_at", forecast.max.start),
+            ] {
+                let value = match start {
+                    Some(start) => Value::datetime(start, None),
+                    None => Value::text(String::new()),
+                };
+                values.insert(key.into(), value);
+            }
+
+            values.insert("forecast_count".into(), Value::number(forecast.count));
+            if let Some(precip_any) = forecast.precip_any {
+                values.insert("precip_any".into(), Value::percents(precip_any));
+            }
+        }
+
+        for (i, period) in self.periods.into_iter().enumerate() {
+            let n = i + 1;
+            let weather = period.weather;
+            if let Some(start) = period.start {
+                values.insert(format!("time_p{n}").into(), Value::datetime(start, None));
+            }
+            map! { @extend values
+                format!("icon_p{n}") => weather.icon.to_value(icons),
+                format!("weather_p{n}") => Value::text(weather.weather),
+                format!("temp_p{n}") => degrees(weather.temp),
+                format!("apparent_p{n}") => degrees(weather.apparent),
+                format!("humidity_p{n}") => Value::percents(weather.humidity),
+                format!("wind_p{n}") => Value::number(weather.wind),
+                format!("wind_kmh_p{n}") => Value::number(weather.wind_kmh),
+                format!("wind_mph_p{n}") => Value::number(weather.wind_mph),
+                format!("direction_p{n}") => Value::text(convert_wind_direction(weather.wind_direction).into()),
+                format!("wind_dir_p{n}") => Value::text(compass_direction(weather.wind_direction).into()),
+                format!("wind_arrow_p{n}") => arrow(weather.wind_direction),
+            }
+            if let Some(precip_prob) = weather.precip_prob {
+                values.insert(format!("precip_prob_p{n}").into(), Value::percents(precip_prob));
+            }
+        }
+
+        if let Some(updated) = self.updated {
+            map! { @extend values
+                "updated" => Value::text(format_age(Utc::now() - updated)),
+                "updated_at" => Value::datetime(updated, None),
+            }
+        } else {
+            map! { @extend values
+                "updated" => Value::text(String::new()),
+                "updated_at" => Value::text(String::new()),
+            }
+        }
 
+        if let Some(alerts) = self.alerts {
             map! { @extend values
-                "icon_ffin" => Value::icon(forecast.fin.icon.to_icon_str()),
-                "weather_ffin" => Value::text(forecast.fin.weather.clone()),
-                "weather_verbose_ffin" => Value::text(forecast.fin.weather_verbose.clone()),
+                "alert" => Value::text(alerts.event.unwrap_or_default()),
+                "alert_headline" => Value::text(alerts.headline.unwrap_or_default()),
+                "alert_count" => Value::number(alerts.count),
             }
         }
         values
@@ -309,48 +1233,218 @@ impl WeatherResult {
 
 pub async fn run(config: &Config, api: &CommonApi) -> Result<()> {
     let mut actions = api.get_actions()?;
-    api.set_default_actions(&[(MouseButton::Left, None, "toggle_format")])?;
 
-    let mut format = config.format.with_default(" $icon $weather $temp ")?;
+    let default_format = if config.format_icon {
+        FORMAT_ICON
+    } else {
+        DEFAULT_FORMAT
+    };
+    let mut format = config.format.with_default(default_format)?;
     let mut format_alt = match &config.format_alt {
         Some(f) => Some(f.with_default("")?),
         None => None,
     };
+    let views = config
+        .views
+        .iter()
+        .map(|view| view.with_default(""))
+        .collect::<Result<Vec<_>>>()?;
+    // 0 is `format`, and the following ones are `views`
+    let mut view = 0;
 
-    let provider: Box<dyn WeatherProvider + Send + Sync> = match &config.service {
-        WeatherService::MetNo(service_config) => Box::new(met_no::Service::new(service_config)?),
-        WeatherService::OpenWeatherMap(service_config) => {
-            Box::new(open_weather_map::Service::new(config.autolocate, service_config).await?)
-        }
-        WeatherService::Nws(service_config) => {
-            Box::new(nws::Service::new(config.autolocate, service_config).await?)
-        }
-    };
+    let client = http_client(config.proxy.as_deref())?;
+    let block_located = config.block_located()?;
+    let mut providers = Vec::new();
+    for service in std::iter::once(&config.service).chain(&config.fallback) {
+        let provider = service.provider(block_located, client.clone()).await?;
+        providers.push((service.name(), provider));
+    }
+
+    if config.format_alt.is_some() {
+        api.set_default_actions(&[
+            (MouseButton::Left, None, "toggle_format"),
+            (MouseButton::Middle, None, "cycle_view"),
+            (MouseButton::Right, None, "refresh"),
+        ])?;
+    } else if providers.iter().any(|(_, p)| p.has_several_locations()) {
+        api.set_default_actions(&[
+            (MouseButton::Left, None, "cycle_location"),
+            (MouseButton::Middle, None, "cycle_view"),
+            (MouseButton::Right, None, "refresh"),
+        ])?;
+    } else {
+        api.set_default_actions(&[
+            (MouseButton::Middle, None, "cycle_view"),
+            (MouseButton::Right, None, "refresh"),
+        ])?;
+    }
+    // The coordinates of `city`, once they are found
+    let mut geocoded = None;
 
     let autolocate_interval = config.autolocate_interval.unwrap_or(config.interval);
-    let need_forecast = need_forecast(&format, format_alt.as_ref());
+    let need_forecast = need_forecast(
+        std::iter::once(&format)
+            .chain(&format_alt)
+            .chain(&views),
+    );
 
+    let mut jitter = Jitter::new(config.jitter.0);
     let mut timer = config.interval.timer();
+    timer.reset_after(jitter.apply(config.interval.0));
+
+    // The last successful update, shown again while updates keep failing
+    let mut last_good = None;
+    let mut failures = 0;
+    let mut last_refresh: Option<Instant> = None;
 
     loop {
+        // Services with a fallback location can do without autolocation
+        let mut autolocate_error = None;
         let location = if config.autolocate {
-            let fetch = || find_ip_location(autolocate_interval.0);
-            Some(fetch.retry(&ExponentialBuilder::default()).await?)
+            let fetch = || find_ip_location(&client, autolocate_interval.0);
+            match fetch.retry(&ExponentialBuilder::default()).await {
+                Ok(location) => Some(location),
+                Err(err) => {
+                    debug!("autolocation failed: {err}");
+                    autolocate_error = Some(err);
+                    None
+                }
+            }
+        } else if let Some(city) = &config.city {
+            if geocoded.is_none() {
+                match geocode_city(&client, city).await {
+                    Ok(location) => geocoded = Some(location),
+                    Err(err) => {
+                        debug!("geocoding {city:?} failed: {err}");
+                        autolocate_error = Some(err);
+                    }
+                }
+            }
+            geocoded.clone()
         } else {
             None
         };
 
-        let fetch = || provider.get_weather(location.as_ref(), need_forecast);
-        let data = fetch.retry(&ExponentialBuilder::default()).await?;
-        let data_values = data.into_values();
+        let fetched = get_weather(
+            &providers,
+            config.provider_timeout.0,
+            location.as_ref(),
+            need_forecast,
+        )
+        .await
+        .map_err(|err| autolocate_error.unwrap_or(err));
+        let (mut data, failed) = match fetched {
+            Ok(data) => {
+                failures = 0;
+                last_good = Some(data.clone());
+                (data, false)
+            }
+            Err(err) => {
+                failures += 1;
+                match &last_good {
+                    Some(data) if failures <= config.max_failures => {
+                        debug!("update {failures} in a row failed, keeping the last data: {err}");
+                        (data.clone(), true)
+                    }
+                    _ => return Err(err),
+                }
+            }
+        };
+        if let Some(location_name) = &config.location_name {
+            data.location.clone_from(location_name);
+        }
+        data.round(config.temp_precision, config.wind_precision);
+        if let Some(max_len) = config.max_verbose_len {
+            data.truncate_verbose(max_len);
+        }
+        let is_stale = failed
+            || config
+                .stale_after
+                .is_some_and(|stale_after| data.is_stale(stale_after.0));
+        let temp = data.current_weather.temp;
+        let is_freezing = config.freeze_below.is_some_and(|freeze| temp < freeze);
+        let is_hot = config.heat_above.is_some_and(|heat| temp > heat);
+        let is_gusty = config
+            .gust_warn_above
+            .is_some_and(|gust_warn| data.max_gust().is_some_and(|gust| gust > gust_warn));
+        let temp_state = config
+            .temp_color
+            .then(|| config.comfort_levels.state(data.temp_unit.to_celsius(temp)));
+        let condition = data.current_weather.icon.condition();
+        let is_critical_condition =
+            condition.is_some_and(|condition| config.critical_on.contains(&condition));
+        let is_warning_condition =
+            condition.is_some_and(|condition| config.warn_on.contains(&condition));
+        let now = Utc::now();
+        let daylight = data.coordinates.map(|(lat, lon)| {
+            // The date at the location, approximated from its longitude
+            let offset = chrono::Duration::try_seconds((lon * 240.0) as i64).unwrap_or_default();
+            let local = now + offset;
+            sun_times(lat, lon, local.date_naive())
+        });
+        let moon = moon_phase(chrono::Local::now().date_naive());
+        let temp_trend = data.temp_trend(config.trend_horizon, config.trend_threshold);
+        let icon_strip = data.icon_strip(config.icon_strip_len);
+        let humidity_level = config
+            .humidity_levels
+            .level(data.current_weather.humidity);
+        let comfort = data.comfort(&config.comfort_levels);
+        let mut data_values = data.into_values(config);
+        data_values.insert("humidity_level".into(), Value::text(humidity_level.into()));
+        data_values.insert("comfort".into(), Value::text(comfort.into()));
+        data_values.insert("icon_strip".into(), Value::text(icon_strip));
+        match &daylight {
+            Some(Daylight::Times { sunrise, sunset }) => map! { @extend data_values
+                "sunrise" => Value::datetime(*sunrise, None),
+                "sunset" => Value::datetime(*sunset, None),
+            },
+            _ => map! { @extend data_values
+                "sunrise" => Value::text(String::new()),
+                "sunset" => Value::text(String::new()),
+            },
+        }
+        if daylight.is_some_and(|daylight| daylight.is_daylight(now)) {
+            data_values.insert("is_daylight".into(), Value::flag());
+        }
+        map! { @extend data_values
+            "moon_phase" => Value::text(moon.name.into()),
+            "moon_icon" => Value::text(moon.icon.into()),
+        }
+        data_values.insert(
+            "temp_trend".into(),
+            Value::text(temp_trend.unwrap_or_default().into()),
+        );
+        map! { @extend data_values
+            [if is_stale] "is_stale" => Value::flag(),
+            [if is_freezing] "is_freezing" => Value::flag(),
+            [if is_hot] "is_hot" => Value::flag(),
+            [if is_gusty] "is_gusty" => Value::flag(),
+        }
 
         loop {
-            let mut widget = Widget::new().with_format(format.clone());
+            let shown = match view {
+                0 => &format,
+                n => &views[n - 1],
+            };
+            let mut widget = Widget::new().with_format(shown.clone());
             widget.set_values(data_values.clone());
+            widget.state = if is_freezing || is_hot || is_critical_condition {
+                State::Critical
+            } else if is_stale || is_gusty || is_warning_condition {
+                State::Warning
+            } else {
+                temp_state.unwrap_or(State::Idle)
+            };
             api.set_widget(widget)?;
 
             select! {
-                _ = timer.tick() => break,
+                _ = timer.tick() => {
+                    timer.reset_after(jitter.apply(config.interval.0));
+                    for (_, provider) in &providers {
+                        provider.cycle_location();
+                    }
+                    break;
+                }
                 _ = api.wait_for_update_request() => break,
                 Some(action) = actions.recv() => match action.as_ref() {
                         "toggle_format" => {
@@ -358,6 +1452,30 @@ pub async fn run(config: &Config, api: &CommonApi) -> Result<()> {
                                 std::mem::swap(format_alt, &mut format);
                             }
                         }
+                        "cycle_location" => {
+                            let mut changed = false;
+                            for (_, provider) in &providers {
+                                changed |= provider.cycle_location();
+                            }
+                            // Only fetch again if there is another location to show
+                            if changed {
+                                break;
+                            }
+                        }
+                        "cycle_view" => {
+                            view = (view + 1) % (views.len() + 1);
+                        }
+                        "refresh" => {
+                            // Ignore clicks in quick succession, to go easy on the services
+                            let cooldown = config.refresh_cooldown.0;
+                            if last_refresh.is_some_and(|last| last.elapsed() < cooldown) {
+                                debug!("ignoring refresh, the last one was too recent");
+                            } else {
+                                last_refresh = Some(Instant::now());
+                                timer.reset_after(jitter.apply(config.interval.0));
+                                break;
+                            }
+                        }
                         _ => (),
                     }
             }
@@ -365,18 +1483,190 @@ pub async fn run(config: &Config, api: &CommonApi) -> Result<()> {
     }
 }
 
-fn need_forecast(format: &Format, format_alt: Option<&Format>) -> bool {
-    fn has_forecast_key(format: &Format) -> bool {
-        macro_rules! format_suffix {
-            ($($suffix: literal),* $(,)?) => {
-                false
-                $(
-                    || format.contains_key(concat!("temp_f", $suffix))
+/// Fetch the weather once from each of the services, printing what they return
+///
+/// Used by the `--check-weather` command line option.
+pub async fn check(config: &Config) -> Result<()> {
+    let client = http_client(config.proxy.as_deref())?;
+    let block_located = config.block_located()?;
+    let location = if let Some(city) = &config.city {
+        match geocode_city(&client, city).await {
+            Ok(location) => {
+                println!(
+                    "geocoded {city:?}: {} ({}, {})",
+                    location.city, location.latitude, location.longitude
+                );
+                Some(location)
+            }
+            Err(err) => {
+                println!("geocoding {city:?} failed: {err}");
+                None
+            }
+        }
+    } else if config.autolocate {
+        let autolocate_interval = config.autolocate_interval.unwrap_or(config.interval);
+        match find_ip_location(&client, autolocate_interval.0).await {
+            Ok(location) => {
+                println!(
+                    "autolocated: {} ({}, {})",
+                    location.city, location.latitude, location.longitude
+                );
+                Some(location)
+            }
+            Err(err) => {
+                println!("autolocation failed: {err}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut last_error = None;
+    for service in std::iter::once(&config.service).chain(&config.fallback) {
+        let name = service.name();
+        let fetched = match service.provider(block_located, client.clone()).await {
+            Ok(provider) => {
+                let providers = [(name, provider)];
+                get_weather(&providers, config.provider_timeout.0, location.as_ref(), true).await
+            }
+            Err(err) => Err(err),
+        };
+        let data = match fetched {
+            Ok(data) => data,
+            Err(err) => {
+                println!("{name}: error: {err}");
+                last_error = Some(err);
+                continue;
+            }
+        };
+
+        println!("{name}: location: {}", data.location);
+        if let Some((lat, lon)) = data.coordinates {
+            println!("{name}: coordinates: {lat}, {lon}");
+        }
+        if let Some(GridPoint { office, x, y }) = &data.grid_point {
+            println!("{name}: grid point: office = \"{office}\", grid_x = {x}, grid_y = {y}");
+        }
+        let current = &data.current_weather;
+        println!(
+            "{name}: raw: temp {} {}, apparent {}, humidity {}, wind {} at {:?}, pressure {:?} {}",
+            current.temp,
+            data.temp_unit.label(),
+            current.apparent,
+            current.humidity,
+            current.wind,
+            current.wind_direction,
+            current.pressure,
+            data.pressure_unit.label(),
+        );
+        let mut values: Vec<_> = data.into_values(config).into_iter().collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (key, value) in values {
+            println!("{name}: {key} = {:?}", value.inner);
+        }
+    }
+    last_error.map_or(Ok(()), Err)
+}
+
+/// Random offsets for the update interval, so that many bars do not poll in lockstep
+struct Jitter {
+    state: u64,
+    max: Duration,
+}
+
+impl Jitter {
+    fn new(max: Duration) -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        // The hasher is randomly seeded for each process
+        let seed = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        Self::with_seed(max, seed)
+    }
+
+    /// A jitter that always shifts by the same amounts for the same `seed`
+    fn with_seed(max: Duration, seed: u64) -> Self {
+        Self {
+            state: seed | 1,
+            max,
+        }
+    }
+
+    /// Shift `interval` by a random amount between `-max` and `max`
+    fn apply(&mut self, interval: Duration) -> Duration {
+        // xorshift64
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        let max = self.max.as_millis() as u64;
+        let offset = Duration::from_millis(self.state % (2 * max + 1));
+        (interval + offset).saturating_sub(self.max)
+    }
+}
+
+/// Try each provider in turn, returning the result of the first one that succeeds
+async fn get_weather(
+    providers: &[(&'static str, Box<dyn WeatherProvider + Send + Sync + '_>)],
+    timeout: Duration,
+    location: Option<&Coordinates>,
+    need_forecast: bool,
+) -> Result<WeatherResult> {
+    let mut last_error = None;
+    for (name, provider) in providers {
+        debug!("{name}: fetching weather");
+        let fetch = || provider.get_weather(location, need_forecast);
+        let fetch = async {
+            if provider.retries_requests() {
+                fetch().await
+            } else {
+                fetch.retry(&ExponentialBuilder::default()).await
+            }
+        };
+        let result = tokio::time::timeout(timeout, fetch).await;
+        match result {
+            Ok(Ok(data)) => {
+                debug!(
+                    "{name}: weather for {} is {}",
+                    data.location,
+                    data.current_weather.icon.to_icon_str()
+                );
+                return Ok(data);
+            }
+            Ok(Err(err)) => {
+                debug!("{name} failed: {err}");
+                last_error = Some(err);
+            }
+            Err(_) => {
+                debug!("{name} timed out");
+                last_error = Some(Error::new(format!("{name} timed out")));
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| Error::new("No weather service configured")))
+}
+
+fn need_forecast<'a>(formats: impl IntoIterator<Item = &'a Format>) -> bool {
+    fn has_forecast_key(format: &Format) -> bool {
+        macro_rules! format_suffix {
+            ($($suffix: literal),* $(,)?) => {
+                false
+                $(
+                    || format.contains_key(concat!("temp_f", $suffix))
                     || format.contains_key(concat!("apparent_f", $suffix))
                     || format.contains_key(concat!("humidity_f", $suffix))
                     || format.contains_key(concat!("wind_f", $suffix))
                     || format.contains_key(concat!("wind_kmh_f", $suffix))
+                    || format.contains_key(concat!("wind_mph_f", $suffix))
                     || format.contains_key(concat!("direction_f", $suffix))
+                    || format.contains_key(concat!("wind_dir_f", $suffix))
+                    || format.contains_key(concat!("wind_arrow_f", $suffix))
+                    || format.contains_key(concat!("dewpoint_f", $suffix))
+                    || format.contains_key(concat!("pressure_f", $suffix))
+                    || format.contains_key(concat!("visibility_f", $suffix))
+                    || format.contains_key(concat!("wind_gust_f", $suffix))
+                    || format.contains_key(concat!("wind_gust_kmh_f", $suffix))
+                    || format.contains_key(concat!("precip_prob_f", $suffix))
                 )*
             };
         }
@@ -385,8 +1675,79 @@ fn need_forecast(format: &Format, format_alt: Option<&Format>) -> bool {
             || format.contains_key("icon_ffin")
             || format.contains_key("weather_ffin")
             || format.contains_key("weather_verbose_ffin")
+            || format.contains_key("weather_detailed_ffin")
+            || format.contains_key("temp_trend")
+            || format.contains_key("temp_min_at")
+            || format.contains_key("temp_max_at")
+            || format.contains_key("forecast_count")
+            || format.contains_key("precip_any")
+    }
+    formats.into_iter().any(has_forecast_key)
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "lowercase")]
+enum Aggregate {
+    #[default]
+    Mean,
+    Median,
+}
+
+/// Which forecast period the `_ffin` values are taken from
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "snake_case")]
+enum FinalPeriod {
+    /// The period right after the aggregated ones, at `forecast_hours`
+    #[default]
+    Horizon,
+    /// The last of the aggregated periods
+    LastAggregated,
+    /// No final value, for when only the aggregates are wanted
+    #[serde(rename = "none")]
+    Omitted,
+}
+
+impl FinalPeriod {
+    /// Index of the final period, given that `aggregated` periods starting at `start` are
+    /// aggregated out of `len`
+    fn index(self, start: usize, aggregated: usize, len: usize) -> Option<usize> {
+        match self {
+            Self::Horizon => Some((start + aggregated).min(len - 1)),
+            Self::LastAggregated => Some((start + aggregated.max(1) - 1).min(len - 1)),
+            Self::Omitted => None,
+        }
+    }
+}
+
+/// How the `_fmin` and `_fmax` values are picked
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "snake_case")]
+enum Extrema {
+    /// The lowest and highest value of each field, possibly from different periods
+    #[default]
+    PerField,
+    /// All the values of the coldest and of the hottest period
+    PerPeriod,
+}
+
+/// How much each forecast period counts towards the `_favg` values
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "snake_case")]
+enum Weighting {
+    /// Every period counts the same
+    #[default]
+    Uniform,
+    /// Of `n` periods, the `i`th one counts `n - i` times, so nearer periods count more
+    LinearDecay,
+}
+
+impl Weighting {
+    fn weight(self, i: usize, n: usize) -> f64 {
+        match self {
+            Self::Uniform => 1.0,
+            Self::LinearDecay => n.saturating_sub(i) as f64,
+        }
     }
-    has_forecast_key(format) || format_alt.is_some_and(has_forecast_key)
 }
 
 #[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, SmartDefault)]
@@ -397,7 +1758,153 @@ enum UnitSystem {
     Imperial,
 }
 
-#[derive(Deserialize, Clone)]
+impl UnitSystem {
+    /// The temperature unit of this system, °C or °F
+    fn temp_unit(self) -> TempUnit {
+        match self {
+            Self::Metric => TempUnit::Celsius,
+            Self::Imperial => TempUnit::Fahrenheit,
+        }
+    }
+
+    /// The pressure unit of this system, hPa or inHg
+    fn pressure_unit(self) -> PressureUnit {
+        match self {
+            Self::Metric => PressureUnit::Hpa,
+            Self::Imperial => PressureUnit::Inhg,
+        }
+    }
+
+    /// Express a distance in meters in the unit of this system, km or miles
+    fn distance_from_meters(self, meters: f64) -> f64 {
+        match self {
+            Self::Metric => meters / 1000.0,
+            Self::Imperial => meters / (MPH_TO_KPH * 1000.0),
+        }
+    }
+
+    /// Express a speed in km/h in the unit of this system, km/h or mph
+    fn speed_from_kmh(self, kmh: f64) -> f64 {
+        match self {
+            Self::Metric => kmh,
+            Self::Imperial => kmh / MPH_TO_KPH,
+        }
+    }
+
+    fn speed_label(self) -> &'static str {
+        match self {
+            Self::Metric => "km/h",
+            Self::Imperial => "mph",
+        }
+    }
+
+    fn distance_label(self) -> &'static str {
+        match self {
+            Self::Metric => "km",
+            Self::Imperial => "mi",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum TempUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TempUnit {
+    /// Convert a temperature in °C to this unit
+    fn convert_celsius(self, celsius: f64) -> f64 {
+        match self {
+            Self::Celsius => celsius,
+            Self::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            Self::Kelvin => celsius + 273.15,
+        }
+    }
+
+    /// Convert a temperature in this unit to °C
+    fn to_celsius(self, temp: f64) -> f64 {
+        match self {
+            Self::Celsius => temp,
+            Self::Fahrenheit => (temp - 32.0) * 5.0 / 9.0,
+            Self::Kelvin => temp - 273.15,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Celsius => "°C",
+            Self::Fahrenheit => "°F",
+            Self::Kelvin => "K",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum PressureUnit {
+    Hpa,
+    Inhg,
+    Mmhg,
+}
+
+impl PressureUnit {
+    /// Convert a pressure in hPa to this unit
+    fn convert_hpa(self, hpa: f64) -> f64 {
+        match self {
+            Self::Hpa => hpa,
+            Self::Inhg => hpa * HPA_TO_INHG,
+            Self::Mmhg => hpa * HPA_TO_MMHG,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Hpa => "hPa",
+            Self::Inhg => "inHg",
+            Self::Mmhg => "mmHg",
+        }
+    }
+}
+
+/// Formula used to compute the apparent ("feels like") temperature
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "snake_case")]
+enum ApparentTemp {
+    #[default]
+    Australian,
+    HeatIndex,
+    WindChill,
+    /// Heat index when hot and humid, wind chill when cold and windy, and the plain temperature
+    /// otherwise
+    Auto,
+}
+
+impl ApparentTemp {
+    /// Compute the apparent temperature in °C from the temperature in °C, the relative humidity in
+    /// % and the wind speed in km/h. Falls back to the plain temperature if the humidity or the wind
+    /// speed is missing or out of range.
+    fn compute(self, temp: f64, humidity: f64, wind_kmh: f64) -> f64 {
+        // A humidity of 0% usually means it wasn't reported at all
+        let plausible =
+            humidity > 0.0 && humidity <= 100.0 && wind_kmh >= 0.0 && wind_kmh.is_finite();
+        if !plausible {
+            return temp;
+        }
+        match self {
+            Self::Australian => australian_apparent_temp(temp, humidity, wind_kmh / 3.6),
+            Self::HeatIndex => heat_index(temp, humidity),
+            Self::WindChill => wind_chill(temp, wind_kmh),
+            Self::Auto if temp >= 27.0 && humidity >= 40.0 => heat_index(temp, humidity),
+            Self::Auto if temp <= 10.0 && wind_kmh > 4.8 => wind_chill(temp, wind_kmh),
+            Self::Auto => temp,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 struct Coordinates {
     latitude: f64,
     longitude: f64,
@@ -411,7 +1918,7 @@ struct AutolocateResult {
 
 // TODO: might be good to allow for different geolocation services to be used, similar to how we have `service` for the weather API
 /// No-op if last API call was made in the last `interval` seconds.
-async fn find_ip_location(interval: Duration) -> Result<Coordinates> {
+async fn find_ip_location(client: &reqwest::Client, interval: Duration) -> Result<Coordinates> {
     {
         let guard = LAST_AUTOLOCATE.lock().unwrap();
         if let Some(cached) = &*guard {
@@ -442,7 +1949,7 @@ async fn find_ip_location(interval: Duration) -> Result<Coordinates> {
     }
     impl StdError for ApiError {}
 
-    let response: ApiResponse = REQWEST_CLIENT
+    let response: ApiResponse = client
         .get(IP_API_URL)
         .send()
         .await
@@ -473,6 +1980,90 @@ async fn find_ip_location(interval: Duration) -> Result<Coordinates> {
     Ok(location)
 }
 
+/// Find the coordinates of `city`. They are cached on disk for good, since cities don't move.
+async fn geocode_city(client: &reqwest::Client, city: &str) -> Result<Coordinates> {
+    let cache_path = geocode_cache_path(city);
+    if let Some(path) = &cache_path {
+        let cached = tokio::fs::read(path).await.ok();
+        if let Some(location) = cached.and_then(|cached| serde_json::from_slice(&cached).ok()) {
+            debug!("using cached coordinates from {}", path.display());
+            return Ok(location);
+        }
+    }
+
+    let response: GeocodingResponse = client
+        .get(GEOCODING_URL)
+        .query(&[("name", city), ("count", "1"), ("format", "json")])
+        .send()
+        .await
+        .error("Geocoding request failed")?
+        .error_for_status()
+        .error("Geocoding request failed")?
+        .json()
+        .await
+        .error("Failed to parse geocoding result")?;
+    let location = response.first_match(city)?;
+    debug!(
+        "{city:?} is {} at {},{}",
+        location.city, location.latitude, location.longitude
+    );
+
+    if let Some(path) = &cache_path {
+        let stored = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(path, serde_json::to_vec(&location)?).await
+        };
+        if let Err(err) = stored.await {
+            debug!("failed to cache coordinates: {err}");
+        }
+    }
+    Ok(location)
+}
+
+fn geocode_cache_path(city: &str) -> Option<PathBuf> {
+    let name: String = city
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut path = dirs::cache_dir()?;
+    path.push("i3status-rust");
+    path.push(format!("geocode_{name}.json"));
+    Some(path)
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodingResponse {
+    /// Missing if nothing matched
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodingResult {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl GeocodingResponse {
+    fn first_match(self, city: &str) -> Result<Coordinates> {
+        let result = self
+            .results
+            .into_iter()
+            .next()
+            .or_error(|| format!("No city named {city:?} found"))?;
+        Ok(Coordinates {
+            latitude: result.latitude,
+            longitude: result.longitude,
+            city: result.name,
+        })
+    }
+}
+
 // Convert wind direction in azimuth degrees to abbreviation names
 fn convert_wind_direction(direction_opt: Option<f64>) -> &'static str {
     match direction_opt {
@@ -490,6 +2081,25 @@ fn convert_wind_direction(direction_opt: Option<f64>) -> &'static str {
     }
 }
 
+/// Convert wind direction in azimuth degrees to a 16-point compass abbreviation
+fn compass_direction(direction: Option<f64>) -> &'static str {
+    const POINTS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+        "NW", "NNW",
+    ];
+    match direction {
+        Some(direction) => POINTS[(direction.rem_euclid(360.0) / 22.5).round() as usize % 16],
+        None => "-",
+    }
+}
+
+/// Convert wind direction in azimuth degrees to an arrow pointing the same way on an 8-point
+/// compass, e.g. "↗" for the north-east. `None` if there is no direction.
+fn wind_arrow(direction: Option<f64>) -> Option<&'static str> {
+    const ARROWS: [&str; 8] = ["↑", "↗", "→", "↘", "↓", "↙", "←", "↖"];
+    direction.map(|direction| ARROWS[(direction.rem_euclid(360.0) / 45.0).round() as usize % 8])
+}
+
 // Compute the average wind speed and direction
 fn average_wind(winds: &[Wind]) -> Wind {
     let mut north = 0.0;
@@ -516,6 +2126,416 @@ fn average_wind(winds: &[Wind]) -> Wind {
     }
 }
 
+/// Median of the values, or NaN if there are none
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_unstable_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => f64::NAN,
+        len if len % 2 == 0 => (values[mid - 1] + values[mid]) / 2.0,
+        _ => values[mid],
+    }
+}
+
+fn combine_forecasts(
+    data: &[ForecastAggregate],
+    fin: Option<WeatherMoment>,
+    aggregate: Aggregate,
+    weighting: Weighting,
+    couple_apparent: bool,
+    extrema: Extrema,
+) -> Forecast {
+    let mut temp = 0.0;
+    let mut apparent = 0.0;
+    let mut humidity = 0.0;
+    // Malformed values can be NaN or infinite. They are skipped, so that they don't poison the
+    // sums and comparisons, and each field is averaged over the periods that have it.
+    let finite = |value: f64| value.is_finite().then_some(value);
+    let mut skipped = 0;
+    let mut temp_count = 0.0;
+    let mut apparent_count = 0.0;
+    let mut humidity_count = 0.0;
+    let mut wind_weight = 0.0;
+    // Dewpoints, pressures, visibilities, gusts and probabilities of precipitation are not always
+    // reported, so they are averaged over the periods that have them
+    let mut dewpoint = 0.0;
+    let mut dewpoint_count = 0.0;
+    let mut pressure = 0.0;
+    let mut pressure_count = 0.0;
+    let mut visibility = 0.0;
+    let mut visibility_count = 0.0;
+    let mut precip_prob = 0.0;
+    let mut precip_prob_count = 0.0;
+    // Probability that none of the periods with a probability are wet
+    let mut all_dry: Option<f64> = None;
+    let mut wind_north = 0.0;
+    let mut wind_east = 0.0;
+    let mut wind_kmh_north = 0.0;
+    let mut wind_kmh_east = 0.0;
+    let mut wind_mph_north = 0.0;
+    let mut wind_mph_east = 0.0;
+    // Calm periods, or ones without a direction, don't say where the wind comes from
+    let mut wind_count = 0;
+    let mut wind_gust = 0.0;
+    let mut wind_gust_kmh = 0.0;
+    let mut wind_gust_count = 0.0;
+    // The coldest and the hottest periods
+    let mut min_index = None;
+    let mut max_index = None;
+    let mut max = ForecastAggregate {
+        temp: -1000.0,
+        apparent: -1000.0,
+        humidity: 0.0,
+        dewpoint: None,
+        pressure: None,
+        visibility: None,
+        wind: 0.0,
+        wind_kmh: 0.0,
+        wind_mph: 0.0,
+        wind_direction: Some(0.0),
+        wind_gust: None,
+        wind_gust_kmh: None,
+        precip_prob: None,
+        start: None,
+    };
+    let mut min = ForecastAggregate {
+        temp: 1000.0,
+        apparent: 1000.0,
+        humidity: 100.0,
+        dewpoint: None,
+        pressure: None,
+        visibility: None,
+        wind: 1000.0,
+        wind_kmh: 1000.0,
+        wind_mph: 1000.0,
+        wind_direction: Some(0.0),
+        wind_gust: None,
+        wind_gust_kmh: None,
+        precip_prob: None,
+        start: None,
+    };
+    for (i, val) in data.iter().enumerate() {
+        // Weighted summations for averaging
+        let weight = weighting.weight(i, data.len());
+        if let Some(val_temp) = finite(val.temp) {
+            temp += weight * val_temp;
+            temp_count += weight;
+            // A coupled apparent temperature falls back to the temperature if it's malformed
+            let coupled = finite(val.apparent).unwrap_or(val_temp);
+            if val_temp > max.temp {
+                max_index = Some(i);
+                max.temp = val_temp;
+                max.start = val.start;
+                if couple_apparent {
+                    max.apparent = coupled;
+                }
+            }
+            if val_temp < min.temp {
+                min_index = Some(i);
+                min.temp = val_temp;
+                min.start = val.start;
+                if couple_apparent {
+                    min.apparent = coupled;
+                }
+            }
+        } else {
+            skipped += 1;
+        }
+        if let Some(val_apparent) = finite(val.apparent) {
+            apparent += weight * val_apparent;
+            apparent_count += weight;
+            if !couple_apparent {
+                max.apparent = max.apparent.max(val_apparent);
+                min.apparent = min.apparent.min(val_apparent);
+            }
+        } else {
+            skipped += 1;
+        }
+        if let Some(val_humidity) = finite(val.humidity) {
+            humidity += weight * val_humidity;
+            humidity_count += weight;
+            max.humidity = max.humidity.max(val_humidity);
+            min.humidity = min.humidity.min(val_humidity);
+        } else {
+            skipped += 1;
+        }
+        if let Some(val_precip_prob) = val.precip_prob.and_then(finite) {
+            precip_prob += weight * val_precip_prob;
+            precip_prob_count += weight;
+            if Some(val_precip_prob) > max.precip_prob {
+                max.precip_prob = Some(val_precip_prob);
+            }
+            if min.precip_prob.is_none() || Some(val_precip_prob) < min.precip_prob {
+                min.precip_prob = Some(val_precip_prob);
+            }
+            let dry = 1.0 - val_precip_prob.clamp(0.0, 100.0) / 100.0;
+            all_dry = Some(all_dry.unwrap_or(1.0) * dry);
+        } else if val.precip_prob.is_some() {
+            skipped += 1;
+        }
+        if let Some(val_dewpoint) = val.dewpoint.and_then(finite) {
+            dewpoint += weight * val_dewpoint;
+            dewpoint_count += weight;
+            if Some(val_dewpoint) > max.dewpoint {
+                max.dewpoint = Some(val_dewpoint);
+            }
+            if min.dewpoint.is_none() || Some(val_dewpoint) < min.dewpoint {
+                min.dewpoint = Some(val_dewpoint);
+            }
+        }
+        if let Some(val_pressure) = val.pressure.and_then(finite) {
+            pressure += weight * val_pressure;
+            pressure_count += weight;
+            if Some(val_pressure) > max.pressure {
+                max.pressure = Some(val_pressure);
+            }
+            if min.pressure.is_none() || Some(val_pressure) < min.pressure {
+                min.pressure = Some(val_pressure);
+            }
+        }
+        if let Some(val_visibility) = val.visibility.and_then(finite) {
+            visibility += weight * val_visibility;
+            visibility_count += weight;
+            if Some(val_visibility) > max.visibility {
+                max.visibility = Some(val_visibility);
+            }
+            if min.visibility.is_none() || Some(val_visibility) < min.visibility {
+                min.visibility = Some(val_visibility);
+            }
+        }
+        let wind_direction = val.wind_direction.and_then(finite);
+        if [val.wind, val.wind_kmh, val.wind_mph]
+            .iter()
+            .all(|speed| speed.is_finite())
+        {
+            let (sin, cos) = wind_direction.unwrap_or_default().to_radians().sin_cos();
+            wind_weight += weight;
+            wind_north += weight * val.wind * cos;
+            wind_east += weight * val.wind * sin;
+            wind_kmh_north += weight * val.wind_kmh * cos;
+            wind_kmh_east += weight * val.wind_kmh * sin;
+            wind_mph_north += weight * val.wind_mph * cos;
+            wind_mph_east += weight * val.wind_mph * sin;
+            if wind_direction.is_some() && val.wind > 0.0 {
+                wind_count += 1;
+            }
+            if val.wind > max.wind {
+                max.wind_direction = wind_direction;
+                max.wind = val.wind;
+                max.wind_kmh = val.wind_kmh;
+                max.wind_mph = val.wind_mph;
+            }
+            if val.wind < min.wind {
+                min.wind_direction = wind_direction;
+                min.wind = val.wind;
+                min.wind_kmh = val.wind_kmh;
+                min.wind_mph = val.wind_mph;
+            }
+        } else {
+            skipped += 1;
+        }
+        if let (Some(gust), Some(gust_kmh)) = (
+            val.wind_gust.and_then(finite),
+            val.wind_gust_kmh.and_then(finite),
+        ) {
+            wind_gust += weight * gust;
+            wind_gust_kmh += weight * gust_kmh;
+            wind_gust_count += weight;
+            if Some(gust) > max.wind_gust {
+                max.wind_gust = Some(gust);
+                max.wind_gust_kmh = Some(gust_kmh);
+            }
+            if min.wind_gust.is_none() || Some(gust) < min.wind_gust {
+                min.wind_gust = Some(gust);
+                min.wind_gust_kmh = Some(gust_kmh);
+            }
+        }
+    }
+    if skipped > 0 {
+        warn!("skipped {skipped} malformed forecast values");
+    }
+
+    if extrema == Extrema::PerPeriod {
+        let whole_period = |i: usize| {
+            let val = &data[i];
+            ForecastAggregate {
+                apparent: finite(val.apparent).unwrap_or(val.temp),
+                ..val.clone()
+            }
+        };
+        if let Some(i) = min_index {
+            min = whole_period(i);
+        }
+        if let Some(i) = max_index {
+            max = whole_period(i);
+        }
+    }
+
+    let mut avg = ForecastAggregate {
+        temp: temp / temp_count,
+        apparent: apparent / apparent_count,
+        humidity: humidity / humidity_count,
+        dewpoint: (dewpoint_count > 0.0).then(|| dewpoint / dewpoint_count),
+        pressure: (pressure_count > 0.0).then(|| pressure / pressure_count),
+        visibility: (visibility_count > 0.0).then(|| visibility / visibility_count),
+        precip_prob: (precip_prob_count > 0.0).then(|| precip_prob / precip_prob_count),
+        wind: wind_east.hypot(wind_north) / wind_weight,
+        wind_kmh: wind_kmh_east.hypot(wind_kmh_north) / wind_weight,
+        wind_mph: wind_mph_east.hypot(wind_mph_north) / wind_weight,
+        wind_direction: (wind_count > 0)
+            .then(|| wind_east.atan2(wind_north).to_degrees().rem_euclid(360.0)),
+        wind_gust: (wind_gust_count > 0.0).then(|| wind_gust / wind_gust_count),
+        wind_gust_kmh: (wind_gust_count > 0.0).then(|| wind_gust_kmh / wind_gust_count),
+        start: None,
+    };
+    if aggregate == Aggregate::Median {
+        // The wind direction is still taken from the vector average
+        let finite_median = |field: fn(&ForecastAggregate) -> f64| {
+            median(data.iter().map(field).filter(|value| value.is_finite()).collect())
+        };
+        avg.temp = finite_median(|val| val.temp);
+        avg.apparent = finite_median(|val| val.apparent);
+        avg.humidity = finite_median(|val| val.humidity);
+        avg.wind = finite_median(|val| val.wind);
+        avg.wind_kmh = finite_median(|val| val.wind_kmh);
+        avg.wind_mph = finite_median(|val| val.wind_mph);
+    }
+    Forecast {
+        avg,
+        min,
+        max,
+        fin,
+        count: data.len(),
+        precip_any: all_dry.map(|all_dry| (1.0 - all_dry) * 100.0),
+    }
+}
+
+fn parse_coordinates(lat: &str, lon: &str) -> Option<(f64, f64)> {
+    Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?))
+}
+
+/// When the sun is up on a given day
+#[derive(Debug, PartialEq)]
+enum Daylight {
+    Times {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+    },
+    /// The sun does not set
+    PolarDay,
+    /// The sun does not rise
+    PolarNight,
+}
+
+impl Daylight {
+    fn is_daylight(&self, now: DateTime<Utc>) -> bool {
+        match self {
+            Self::Times { sunrise, sunset } => (*sunrise..*sunset).contains(&now),
+            Self::PolarDay => true,
+            Self::PolarNight => false,
+        }
+    }
+}
+
+/// Compute sunrise and sunset with the [sunrise
+/// equation](https://en.wikipedia.org/wiki/Sunrise_equation), accurate to a minute or two
+fn sun_times(lat: f64, lon: f64, date: chrono::NaiveDate) -> Daylight {
+    const UNIX_EPOCH_JD: f64 = 2440587.5;
+    const J2000_JD: f64 = 2451545.0;
+
+    let days = (date - chrono::NaiveDate::default()).num_days() as f64;
+    // Days since J2000 to the mean solar noon at the longitude
+    let mean_noon = days + UNIX_EPOCH_JD + 0.5 - J2000_JD + 0.0008 - lon / 360.0;
+
+    let anomaly = (357.5291 + 0.98560028 * mean_noon).rem_euclid(360.0).to_radians();
+    let center = 1.9148 * anomaly.sin()
+        + 0.02 * (2.0 * anomaly).sin()
+        + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic_lon = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit =
+        J2000_JD + mean_noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_lon).sin();
+    let declination = (ecliptic_lon.sin() * 23.4397f64.to_radians().sin()).asin();
+
+    let lat = lat.to_radians();
+    let cos_hour_angle = ((-0.833f64).to_radians().sin() - lat.sin() * declination.sin())
+        / (lat.cos() * declination.cos());
+    if cos_hour_angle < -1.0 {
+        return Daylight::PolarDay;
+    }
+    if cos_hour_angle > 1.0 {
+        return Daylight::PolarNight;
+    }
+    let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+    let to_time = |jd: f64| {
+        DateTime::from_timestamp(((jd - UNIX_EPOCH_JD) * 86400.0) as i64, 0).unwrap_or_default()
+    };
+    Daylight::Times {
+        sunrise: to_time(transit - half_day),
+        sunset: to_time(transit + half_day),
+    }
+}
+
+/// The phase of the moon on a given day
+#[derive(Debug, PartialEq)]
+struct MoonPhase {
+    /// Fraction of the lunar cycle elapsed since the new moon, from 0 to 1
+    fraction: f64,
+    name: &'static str,
+    icon: &'static str,
+}
+
+/// Compute the phase of the moon at noon UTC of `date`, from the mean length of the lunar cycle
+/// and a known new moon. This is accurate to about a day.
+fn moon_phase(date: chrono::NaiveDate) -> MoonPhase {
+    const SYNODIC_MONTH: f64 = 29.530588853;
+    // The new moon of 2000-01-06 18:14 UTC, in days since the Unix epoch
+    const NEW_MOON: f64 = 10962.76;
+    const PHASES: [(&str, &str); 8] = [
+        ("New Moon", "🌑"),
+        ("Waxing Crescent", "🌒"),
+        ("First Quarter", "🌓"),
+        ("Waxing Gibbous", "🌔"),
+        ("Full Moon", "🌕"),
+        ("Waning Gibbous", "🌖"),
+        ("Last Quarter", "🌗"),
+        ("Waning Crescent", "🌘"),
+    ];
+
+    let days = (date - chrono::NaiveDate::default()).num_days() as f64 + 0.5;
+    let fraction = ((days - NEW_MOON) / SYNODIC_MONTH).rem_euclid(1.0);
+    let (name, icon) = PHASES[(fraction * 8.0).round() as usize % 8];
+    MoonPhase {
+        fraction,
+        name,
+        icon,
+    }
+}
+
+/// Cut `text` down to `max_len` grapheme clusters, so that accented letters are never split,
+/// ending with an ellipsis if anything was cut
+fn truncate(text: &mut String, max_len: usize) {
+    if text.graphemes(true).count() > max_len {
+        *text = text
+            .graphemes(true)
+            .take(max_len.saturating_sub(1))
+            .chain(std::iter::once("…"))
+            .collect();
+    }
+}
+
+/// Format how long ago something happened, e.g. "12m ago"
+fn format_age(age: chrono::Duration) -> String {
+    let minutes = age.num_minutes().max(0);
+    match minutes {
+        0 => "just now".into(),
+        1..=59 => format!("{minutes}m ago"),
+        60..=1439 => format!("{}h ago", minutes / 60),
+        _ => format!("{}d ago", minutes / 1440),
+    }
+}
+
 /// Compute the Australian Apparent Temperature from metric units
 fn australian_apparent_temp(temp: f64, humidity: f64, wind_speed: f64) -> f64 {
     let exponent = 17.27 * temp / (237.7 + temp);
@@ -523,6 +2543,41 @@ fn australian_apparent_temp(temp: f64, humidity: f64, wind_speed: f64) -> f64 {
     temp + 0.33 * water_vapor_pressure - 0.7 * wind_speed - 4.0
 }
 
+/// Compute the NWS heat index from the temperature in °C and the relative humidity in %
+///
+/// See <https://www.wpc.ncep.noaa.gov/html/heatindex_equation.shtml>
+fn heat_index(temp: f64, humidity: f64) -> f64 {
+    let t = temp * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let index = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut index = -42.379 + 2.04901523 * t + 10.14333127 * rh
+            - 0.22475541 * t * rh
+            - 0.00683783 * t * t
+            - 0.05481717 * rh * rh
+            + 0.00122874 * t * t * rh
+            + 0.00085282 * t * rh * rh
+            - 0.00000199 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            index -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            index += (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0);
+        }
+        index
+    };
+    (index - 32.0) * 5.0 / 9.0
+}
+
+/// Compute the NWS wind chill from the temperature in °C and the wind speed in km/h
+///
+/// See <https://www.weather.gov/media/epz/wxcalc/windChill.pdf>
+fn wind_chill(temp: f64, wind_kmh: f64) -> f64 {
+    let wind = wind_kmh.powf(0.16);
+    13.12 + 0.6215 * temp - 11.37 * wind + 0.3965 * temp * wind
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -602,4 +2657,975 @@ mod tests {
             degrees += 15.0;
         }
     }
+
+    fn winds(winds: &[(f64, Option<f64>)]) -> Vec<ForecastAggregate> {
+        let mut data = ramp(&vec![20.0; winds.len()]);
+        for (val, &(wind, direction)) in data.iter_mut().zip(winds) {
+            val.wind = wind;
+            val.wind_kmh = wind;
+            val.wind_mph = wind / MPH_TO_KPH;
+            val.wind_direction = direction;
+        }
+        data
+    }
+
+    #[test]
+    fn test_combine_forecasts_wind_wrap() {
+        for (low, high, expected) in [(350.0, 10.0, 0.0), (359.0, 1.0, 0.0), (340.0, 0.0, 350.0)] {
+            let data = winds(&[(10.0, Some(low)), (10.0, Some(high))]);
+            let forecast = combine_forecasts(
+                &data,
+                Some(moment()),
+                Aggregate::Mean,
+                Weighting::Uniform,
+                false,
+                Extrema::PerField,
+            );
+            let direction = forecast.avg.wind_direction.unwrap();
+            assert!((0.0..=360.0).contains(&direction));
+            // Distance to the expected direction, going either way around the circle
+            let off = (direction - expected).rem_euclid(360.0);
+            assert!(off.min(360.0 - off) < 0.01, "{low} and {high} gave {direction}");
+            assert!(forecast.avg.wind > 9.0);
+        }
+        // Cancelling east-west components can land on either side of north
+        let data = winds(&[(10.0, Some(350.0)), (10.0, Some(10.0))]);
+        let forecast = combine_forecasts(
+            &data,
+            Some(moment()),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        );
+        let direction = forecast.avg.wind_direction.unwrap();
+        assert!(!(0.01..=359.99).contains(&direction));
+        assert_eq!(compass_direction(Some(direction)), "N");
+    }
+
+    #[test]
+    fn test_combine_forecasts_calm() {
+        let data = winds(&[(0.0, Some(90.0)), (0.0, None), (0.0, Some(270.0))]);
+        let forecast = combine_forecasts(
+            &data,
+            Some(moment()),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        );
+        assert_eq!(forecast.avg.wind, 0.0);
+        assert_eq!(forecast.avg.wind_direction, None);
+
+        let data = winds(&[(0.0, None), (5.0, Some(90.0))]);
+        let forecast = combine_forecasts(
+            &data,
+            Some(moment()),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        );
+        assert!((forecast.avg.wind_direction.unwrap() - 90.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_precip_any() {
+        let mut data = ramp(&[10.0, 10.0, 10.0]);
+        for (val, precip_prob) in data.iter_mut().zip([20.0, 50.0, 0.0]) {
+            val.precip_prob = Some(precip_prob);
+        }
+        let combine = |data: &[ForecastAggregate]| {
+            combine_forecasts(
+                data,
+                None,
+                Aggregate::Mean,
+                Weighting::Uniform,
+                false,
+                Extrema::PerField,
+            )
+        };
+        // 1 - 0.8 * 0.5 * 1.0
+        let precip_any = combine(&data).precip_any.unwrap();
+        assert!((precip_any - 60.0).abs() < 1e-9);
+
+        data[2].precip_prob = Some(100.0);
+        assert_eq!(combine(&data).precip_any, Some(100.0));
+
+        // Without any probabilities, there's nothing to combine
+        let data = ramp(&[10.0, 10.0]);
+        assert_eq!(combine(&data).precip_any, None);
+    }
+
+    #[test]
+    fn test_precip_prob() {
+        let combine = |data: &[ForecastAggregate]| {
+            combine_forecasts(
+                data,
+                None,
+                Aggregate::Mean,
+                Weighting::Uniform,
+                false,
+                Extrema::PerField,
+            )
+        };
+        // Like met.no and OpenWeatherMap, which don't report any
+        let forecast = combine(&ramp(&[10.0, 20.0]));
+        assert_eq!(forecast.avg.precip_prob, None);
+        assert_eq!(forecast.min.precip_prob, None);
+        assert_eq!(forecast.max.precip_prob, None);
+
+        // Periods without a probability are left out
+        let mut data = ramp(&[10.0, 20.0, 30.0]);
+        data[0].precip_prob = Some(20.0);
+        data[2].precip_prob = Some(60.0);
+        let forecast = combine(&data);
+        assert_eq!(forecast.avg.precip_prob, Some(40.0));
+        assert_eq!(forecast.min.precip_prob, Some(20.0));
+        assert_eq!(forecast.max.precip_prob, Some(60.0));
+    }
+
+    #[test]
+    fn test_extrema_mode() {
+        let mut data = ramp(&[15.0, 10.0, 25.0, 20.0]);
+        let fields = [(40.0, 5.0), (80.0, 10.0), (30.0, 15.0), (90.0, 2.0)];
+        for (val, (humidity, wind)) in data.iter_mut().zip(fields) {
+            val.humidity = humidity;
+            val.wind = wind;
+        }
+        let combine = |extrema| {
+            combine_forecasts(
+                &data,
+                Some(moment()),
+                Aggregate::Mean,
+                Weighting::Uniform,
+                false,
+                extrema,
+            )
+        };
+
+        let per_field = combine(Extrema::PerField);
+        assert_eq!(per_field.min.temp, 10.0);
+        assert_eq!(per_field.min.humidity, 30.0);
+        assert_eq!(per_field.min.wind, 2.0);
+        assert_eq!(per_field.max.temp, 25.0);
+        assert_eq!(per_field.max.humidity, 90.0);
+        assert_eq!(per_field.max.wind, 15.0);
+
+        // The values of the coldest and the hottest periods go together
+        let per_period = combine(Extrema::PerPeriod);
+        assert_eq!(per_period.min.temp, 10.0);
+        assert_eq!(per_period.min.humidity, 80.0);
+        assert_eq!(per_period.min.wind, 10.0);
+        assert_eq!(per_period.max.temp, 25.0);
+        assert_eq!(per_period.max.humidity, 30.0);
+        assert_eq!(per_period.max.wind, 15.0);
+        assert_eq!(per_period.avg.temp, per_field.avg.temp);
+
+        let extrema: Extrema = serde_json::from_str("\"per_period\"").unwrap();
+        assert_eq!(extrema, Extrema::PerPeriod);
+    }
+
+    #[test]
+    fn test_combine_forecasts_nan() {
+        let mut data = ramp(&[10.0, 20.0, 30.0]);
+        data[1].temp = f64::NAN;
+        data[1].humidity = f64::NAN;
+        data[2].wind = f64::INFINITY;
+        data[2].dewpoint = Some(f64::NAN);
+        data[0].dewpoint = Some(5.0);
+        for aggregate in [Aggregate::Mean, Aggregate::Median] {
+            let forecast = combine_forecasts(
+                &data,
+                Some(moment()),
+                aggregate,
+                Weighting::Uniform,
+                false,
+                Extrema::PerField,
+            );
+            assert_eq!(forecast.avg.temp, 20.0);
+            assert_eq!(forecast.min.temp, 10.0);
+            assert_eq!(forecast.max.temp, 30.0);
+            assert_eq!(forecast.avg.apparent, 20.0);
+            assert_eq!(forecast.avg.humidity, 50.0);
+            assert_eq!(forecast.max.humidity, 50.0);
+            assert_eq!(forecast.avg.dewpoint, Some(5.0));
+            assert_eq!(forecast.max.dewpoint, Some(5.0));
+            assert!((forecast.avg.wind - 10.0).abs() < 1e-9);
+            assert_eq!(forecast.max.wind, 10.0);
+        }
+
+        // The apparent temperature of the coldest period is malformed
+        data[0].apparent = f64::NAN;
+        let coupled = combine_forecasts(
+            &data,
+            Some(moment()),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            true,
+            Extrema::PerField,
+        );
+        assert_eq!(coupled.min.apparent, 10.0);
+        assert_eq!(coupled.max.apparent, 30.0);
+        assert_eq!(coupled.avg.apparent, 25.0);
+    }
+
+    fn to_fahrenheit(temp: f64) -> f64 {
+        temp * 9.0 / 5.0 + 32.0
+    }
+
+    fn from_fahrenheit(temp: f64) -> f64 {
+        (temp - 32.0) * 5.0 / 9.0
+    }
+
+    #[test]
+    fn test_is_precip() {
+        for icon in WeatherIcon::KINDS {
+            let expected = matches!(icon.to_word(), "Rain" | "Sleet" | "Snow" | "Thunder");
+            assert_eq!(icon.is_precip(), expected, "{}", icon.to_word());
+            assert!(!(icon.is_precip() && icon.is_clear()));
+        }
+        assert!(WeatherIcon::Clear { is_night: true }.is_clear());
+        assert!(WeatherIcon::Thunder { is_night: true }.is_precip());
+        assert!(!WeatherIcon::PartlyCloudy { is_night: false }.is_clear());
+    }
+
+    #[test]
+    fn test_wind_arrow() {
+        assert_eq!(wind_arrow(None), None);
+        let arrows = ["↑", "↗", "→", "↘", "↓", "↙", "←", "↖", "↑"];
+        for (i, arrow) in arrows.into_iter().enumerate() {
+            let direction = i as f64 * 45.0;
+            assert_eq!(wind_arrow(Some(direction)), Some(arrow), "{direction}");
+            // Each arrow covers 22.5° on either side
+            assert_eq!(wind_arrow(Some(direction + 22.49)), Some(arrow), "{direction}");
+            assert_eq!(wind_arrow(Some(direction - 22.5)), Some(arrow), "{direction}");
+        }
+        assert_eq!(wind_arrow(Some(22.5)), Some("↗"));
+        assert_eq!(wind_arrow(Some(-45.0)), Some("↖"));
+    }
+
+    #[test]
+    fn test_compass_direction() {
+        assert_eq!(compass_direction(None), "-");
+        assert_eq!(compass_direction(Some(0.0)), "N");
+        assert_eq!(compass_direction(Some(11.24)), "N");
+        assert_eq!(compass_direction(Some(11.25)), "NNE");
+        assert_eq!(compass_direction(Some(33.74)), "NNE");
+        assert_eq!(compass_direction(Some(33.75)), "NE");
+        assert_eq!(compass_direction(Some(202.5)), "SSW");
+        assert_eq!(compass_direction(Some(348.74)), "NNW");
+        assert_eq!(compass_direction(Some(348.75)), "N");
+        assert_eq!(compass_direction(Some(360.0)), "N");
+        assert_eq!(compass_direction(Some(-22.5)), "NNW");
+    }
+
+    #[test]
+    fn test_compass_direction_of_average() {
+        // The average of NW and NE winds of equal speed is N, not S
+        let wind = average_wind(&[
+            Wind {
+                speed: 1.0,
+                degrees: Some(315.0),
+            },
+            Wind {
+                speed: 1.0,
+                degrees: Some(45.0),
+            },
+        ]);
+        assert_eq!(compass_direction(wind.degrees), "N");
+    }
+
+    #[test]
+    fn test_humidity_levels() {
+        let levels = HumidityLevels::default();
+        assert_eq!(levels.level(20.0), "dry");
+        assert_eq!(levels.level(30.0), "comfortable");
+        assert_eq!(levels.level(60.0), "comfortable");
+        assert_eq!(levels.level(70.0), "humid");
+        assert_eq!(levels.level(90.0), "oppressive");
+
+        let levels: HumidityLevels = toml::from_str("dry_below = 40").unwrap();
+        assert_eq!(levels.level(35.0), "dry");
+        assert_eq!(levels.level(70.0), "humid");
+    }
+
+    #[test]
+    fn test_comfort() {
+        let at = |temp, wind_kmh| WeatherResult {
+            location: String::new(),
+            location_distance: None,
+            location_bearing: None,
+            current_weather: WeatherMoment {
+                temp,
+                apparent: temp,
+                wind_kmh,
+                ..moment()
+            },
+            forecast: None,
+            alerts: None,
+            updated: None,
+            periods: Vec::new(),
+            coordinates: None,
+            temp_unit: TempUnit::Celsius,
+            pressure_unit: PressureUnit::Hpa,
+            units: UnitSystem::Metric,
+            provider: "",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
+        };
+        let levels = ComfortLevels::default();
+        assert_eq!(at(-5.0, 0.0).comfort(&levels), "freezing");
+        assert_eq!(at(5.0, 0.0).comfort(&levels), "cold");
+        assert_eq!(at(15.0, 0.0).comfort(&levels), "cool");
+        assert_eq!(at(21.0, 0.0).comfort(&levels), "mild");
+        assert_eq!(at(27.0, 0.0).comfort(&levels), "warm");
+        assert_eq!(at(35.0, 0.0).comfort(&levels), "hot");
+        // The wind makes it feel colder
+        assert_eq!(at(2.0, 40.0).comfort(&levels), "freezing");
+
+        // Imperial data against metric thresholds, and the other way around
+        let fahrenheit = WeatherResult {
+            temp_unit: TempUnit::Fahrenheit,
+            pressure_unit: PressureUnit::Inhg,
+            units: UnitSystem::Imperial,
+            ..at(70.0, 0.0)
+        };
+        assert_eq!(fahrenheit.comfort(&levels), "mild");
+        let levels: ComfortLevels =
+            toml::from_str("units = \"imperial\"\nwarm_above = 60\nhot_above = 80").unwrap();
+        assert_eq!(fahrenheit.comfort(&levels), "warm");
+        assert_eq!(at(21.0, 0.0).comfort(&levels), "warm");
+    }
+
+    fn weather_result() -> WeatherResult {
+        WeatherResult {
+            location: String::new(),
+            location_distance: None,
+            location_bearing: None,
+            current_weather: moment(),
+            forecast: None,
+            alerts: None,
+            updated: None,
+            periods: Vec::new(),
+            coordinates: None,
+            temp_unit: TempUnit::Celsius,
+            pressure_unit: PressureUnit::Hpa,
+            units: UnitSystem::Metric,
+            provider: "",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
+        }
+    }
+
+    #[test]
+    fn test_max_gust() {
+        let mut data = weather_result();
+        assert_eq!(data.max_gust(), None);
+        data.current_weather.wind_gust = Some(30.0);
+        assert_eq!(data.max_gust(), Some(30.0));
+
+        let mut periods = ramp(&[10.0, 12.0]);
+        periods[1].wind_gust = Some(45.0);
+        periods[1].wind_gust_kmh = Some(45.0);
+        data.forecast = Some(combine_forecasts(
+            &periods,
+            None,
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        ));
+        assert_eq!(data.max_gust(), Some(45.0));
+        data.current_weather.wind_gust = None;
+        assert_eq!(data.max_gust(), Some(45.0));
+    }
+
+    #[test]
+    fn test_city() {
+        let config = |toml: &str| toml::from_str::<Config>(toml).unwrap();
+        let located = |toml| config(toml).block_located().map_err(|err| err.message.unwrap());
+        assert_eq!(located("service.name = \"metno\""), Ok(false));
+        assert_eq!(
+            located("city = \"Berlin\"\nservice.name = \"metno\""),
+            Ok(true)
+        );
+        assert_eq!(
+            located("city = \"Berlin\"\nautolocate = true\nservice.name = \"metno\""),
+            Err("city cannot be combined with autolocate".into())
+        );
+        assert_eq!(
+            located(
+                r#"
+                city = "Berlin"
+                service.name = "metno"
+                [[fallback]]
+                name = "nws"
+                coordinates = ["42.36", "-71.06"]
+                "#
+            ),
+            Err("city and coordinates cannot both be set".into())
+        );
+
+        let response: GeocodingResponse = serde_json::from_str(
+            r#"{
+                "results": [
+                    {
+                        "id": 2950159,
+                        "name": "Berlin",
+                        "latitude": 52.52437,
+                        "longitude": 13.41053,
+                        "country": "Germany"
+                    }
+                ],
+                "generationtime_ms": 0.5
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response.first_match("berlin").unwrap(),
+            Coordinates {
+                latitude: 52.52437,
+                longitude: 13.41053,
+                city: "Berlin".into(),
+            }
+        );
+        let response: GeocodingResponse =
+            serde_json::from_str(r#"{ "generationtime_ms": 0.5 }"#).unwrap();
+        let err = response.first_match("Atlantis").unwrap_err();
+        assert_eq!(err.message.unwrap(), "No city named \"Atlantis\" found");
+
+        let path = geocode_cache_path(" New York/NY ").unwrap();
+        assert!(path.ends_with("i3status-rust/geocode_new_york_ny.json"));
+    }
+
+    #[test]
+    fn test_unit_labels() {
+        let config: Config = toml::from_str(
+            r#"
+            [service]
+            name = "openmeteo"
+            coordinates = ["0", "0"]
+            "#,
+        )
+        .unwrap();
+        let result = |units| WeatherResult {
+            units,
+            ..weather_result()
+        };
+        let label = |values: &Values, key: &str| match &values[key].inner {
+            crate::formatting::value::ValueInner::Text(text) => text.clone(),
+            _ => panic!("{key} is not a text"),
+        };
+        let metric = result(UnitSystem::Metric).into_values(&config);
+        assert_eq!(label(&metric, "wind_unit"), "km/h");
+        assert_eq!(label(&metric, "distance_unit"), "km");
+        assert_eq!(label(&metric, "humidity_unit"), "%");
+        let imperial = result(UnitSystem::Imperial).into_values(&config);
+        assert_eq!(label(&imperial, "wind_unit"), "mph");
+        assert_eq!(label(&imperial, "distance_unit"), "mi");
+    }
+
+    #[test]
+    fn test_grid_point_keys() {
+        let config: Config = toml::from_str("service.name = \"nws\"").unwrap();
+        let values = weather_result().into_values(&config);
+        assert!(!values.contains_key("office"));
+        let result = WeatherResult {
+            grid_point: Some(GridPoint {
+                office: "BOX".into(),
+                x: 71,
+                y: 76,
+            }),
+            ..weather_result()
+        };
+        let values = result.into_values(&config);
+        let text = |key: &str| match &values[key].inner {
+            crate::formatting::value::ValueInner::Text(text) => text.clone(),
+            _ => panic!("{key} is not a text"),
+        };
+        assert_eq!(text("office"), "BOX");
+        assert_eq!(text("grid"), "71,76");
+    }
+
+    #[test]
+    fn test_temp_state() {
+        let levels = ComfortLevels::default();
+        assert_eq!(levels.state(-5.0), State::Info);
+        assert_eq!(levels.state(5.0), State::Info);
+        assert_eq!(levels.state(15.0), State::Good);
+        assert_eq!(levels.state(21.0), State::Good);
+        assert_eq!(levels.state(27.0), State::Warning);
+        assert_eq!(levels.state(35.0), State::Critical);
+        let levels: ComfortLevels = toml::from_str("units = \"imperial\"\nhot_above = 80").unwrap();
+        assert_eq!(levels.state(28.0), State::Critical);
+    }
+
+    #[test]
+    fn test_round_to() {
+        let mut value = 18.333333;
+        round_to(&mut value, None);
+        assert_eq!(value, 18.333333);
+        round_to(&mut value, Some(1));
+        assert_eq!(value, 18.3);
+        round_to(&mut value, Some(0));
+        assert_eq!(value, 18.0);
+
+        let mut data = WeatherResult {
+            daily: Some(DailySummary {
+                today_high: Some(24.46),
+                today_low: Some(12.04),
+                tomorrow_high: None,
+                tomorrow_low: Some(9.95),
+            }),
+            ..weather_result()
+        };
+        data.round(Some(1), None);
+        let daily = data.daily.unwrap();
+        assert_eq!(daily.today_high, Some(24.5));
+        assert_eq!(daily.today_low, Some(12.0));
+        assert_eq!(daily.tomorrow_high, None);
+        assert_eq!(daily.tomorrow_low, Some(10.0));
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(chrono::Duration::try_seconds(-30).unwrap()), "just now");
+        assert_eq!(format_age(chrono::Duration::try_seconds(30).unwrap()), "just now");
+        assert_eq!(format_age(chrono::Duration::try_minutes(12).unwrap()), "12m ago");
+        assert_eq!(format_age(chrono::Duration::try_minutes(150).unwrap()), "2h ago");
+        assert_eq!(format_age(chrono::Duration::try_days(3).unwrap()), "3d ago");
+    }
+
+    #[test]
+    fn test_heat_index() {
+        // Reference values from the NWS heat index chart, in °F
+        for (temp, humidity, expected) in [
+            (80.0, 40.0, 80.0),
+            (90.0, 60.0, 100.0),
+            (96.0, 65.0, 121.0),
+            (100.0, 40.0, 109.0),
+            (110.0, 10.0, 105.0),
+        ] {
+            let index = to_fahrenheit(heat_index(from_fahrenheit(temp), humidity));
+            assert!((index - expected).abs() < 1.5, "{temp}°F {humidity}%: {index}");
+        }
+    }
+
+    #[test]
+    fn test_wind_chill() {
+        // Reference values from the NWS wind chill chart, in °F and mph
+        for (temp, wind, expected) in [
+            (40.0, 5.0, 36.0),
+            (0.0, 15.0, -19.0),
+            (-10.0, 30.0, -39.0),
+            (20.0, 60.0, -4.0),
+        ] {
+            let chill = to_fahrenheit(wind_chill(from_fahrenheit(temp), wind * 1.609344));
+            assert!((chill - expected).abs() < 1.0, "{temp}°F {wind}mph: {chill}");
+        }
+    }
+
+    #[test]
+    fn test_apparent_temp_auto() {
+        assert_eq!(ApparentTemp::Auto.compute(20.0, 50.0, 10.0), 20.0);
+        assert_eq!(
+            ApparentTemp::Auto.compute(32.0, 60.0, 10.0),
+            heat_index(32.0, 60.0)
+        );
+        assert_eq!(
+            ApparentTemp::Auto.compute(-5.0, 50.0, 20.0),
+            wind_chill(-5.0, 20.0)
+        );
+        assert_eq!(ApparentTemp::Auto.compute(-5.0, 50.0, 0.0), -5.0);
+    }
+
+    #[test]
+    fn test_apparent_temp_implausible() {
+        let australian = ApparentTemp::Australian;
+        assert_ne!(australian.compute(20.0, 50.0, 0.0), 20.0);
+        assert_eq!(australian.compute(20.0, 0.0, 10.0), 20.0);
+        assert_eq!(australian.compute(20.0, -5.0, 10.0), 20.0);
+        assert_eq!(australian.compute(20.0, 150.0, 10.0), 20.0);
+        assert_eq!(australian.compute(20.0, f64::NAN, 10.0), 20.0);
+        assert_eq!(australian.compute(20.0, 50.0, -1.0), 20.0);
+        assert_eq!(australian.compute(20.0, 50.0, f64::NAN), 20.0);
+        assert_eq!(australian.compute(20.0, 50.0, f64::INFINITY), 20.0);
+        assert_eq!(ApparentTemp::HeatIndex.compute(35.0, 0.0, 0.0), 35.0);
+        assert_eq!(ApparentTemp::WindChill.compute(-5.0, 50.0, -10.0), -5.0);
+    }
+
+    #[test]
+    fn test_median_odd() {
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(vec![5.0, 100.0, 4.0, 6.0, 5.0]), 5.0);
+    }
+
+    #[test]
+    fn test_median_even() {
+        assert_eq!(median(vec![4.0, 1.0, 3.0, 2.0]), 2.5);
+        assert_eq!(median(vec![1.0, 100.0]), 50.5);
+    }
+
+    #[test]
+    fn test_median_empty() {
+        assert!(median(Vec::new()).is_nan());
+    }
+
+    struct Unavailable;
+
+    #[async_trait]
+    impl WeatherProvider for Unavailable {
+        async fn get_weather(&self, _: Option<&Coordinates>, _: bool) -> Result<WeatherResult> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_all_providers_fail() {
+        let providers: Vec<(&'static str, Box<dyn WeatherProvider + Send + Sync>)> = vec![
+            ("first", Box::new(Unavailable)),
+            ("second", Box::new(Unavailable)),
+        ];
+        let err = get_weather(&providers, Duration::from_millis(10), None, false)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.message.as_deref(), Some("second timed out"));
+    }
+
+    #[test]
+    fn test_condition() {
+        let conditions: Vec<Condition> =
+            serde_json::from_str(r#"["thunder", "partly_cloudy"]"#).unwrap();
+        assert_eq!(conditions, [Condition::Thunder, Condition::PartlyCloudy]);
+        assert!(serde_json::from_str::<Condition>(r#""hail""#).is_err());
+
+        let thunder = WeatherIcon::Thunder { is_night: true }.condition();
+        assert_eq!(thunder, Some(Condition::Thunder));
+        let partly_cloudy = WeatherIcon::PartlyCloudy { is_night: false }.condition();
+        assert_eq!(partly_cloudy, Some(Condition::PartlyCloudy));
+        assert_eq!(WeatherIcon::Default.condition(), None);
+    }
+
+    #[test]
+    fn test_icons() {
+        use crate::formatting::value::ValueInner;
+
+        #[derive(Deserialize)]
+        struct Icons {
+            #[serde(deserialize_with = "deserialize_icons")]
+            icons: HashMap<String, String>,
+        }
+
+        let Icons { icons } =
+            toml::from_str("icons = { Snow = \"*\", \"Partly Cloudy\" = \"~\" }").unwrap();
+        let snow = WeatherIcon::Snow { is_night: true }.to_value(&icons);
+        assert!(matches!(snow.inner, ValueInner::Text(text) if text == "*"));
+        let partly = WeatherIcon::PartlyCloudy { is_night: false }.to_value(&icons);
+        assert!(matches!(partly.inner, ValueInner::Text(text) if text == "~"));
+        let rain = WeatherIcon::Rain { is_night: false }.to_value(&icons);
+        assert!(matches!(rain.inner, ValueInner::Icon(icon, None) if icon == "weather_rain"));
+
+        let err = toml::from_str::<Icons>("icons = { Hail = \"*\" }").err().unwrap();
+        assert!(err.to_string().contains("unknown weather condition 'Hail'"));
+    }
+
+    #[test]
+    fn test_format_icon() {
+        let format = FormatConfig::default().with_default(FORMAT_ICON).unwrap();
+        assert!(format.contains_key("icon"));
+        assert!(format.contains_key("temp"));
+        assert!(!format.contains_key("weather"));
+        assert!(!format.contains_key("weather_verbose"));
+
+        // Every kind of weather, by day and by night, has an icon in each icon set
+        let default_icons = crate::icons::Icons::default();
+        let icon_sets = [
+            include_str!("../../files/icons/awesome4.toml"),
+            include_str!("../../files/icons/awesome5.toml"),
+            include_str!("../../files/icons/awesome6.toml"),
+            include_str!("../../files/icons/emoji.toml"),
+            include_str!("../../files/icons/material.toml"),
+            include_str!("../../files/icons/material-nf.toml"),
+        ];
+        for is_night in [false, true] {
+            for icon in [
+                WeatherIcon::Clear { is_night },
+                WeatherIcon::Clouds { is_night },
+                WeatherIcon::PartlyCloudy { is_night },
+                WeatherIcon::Fog { is_night },
+                WeatherIcon::Rain { is_night },
+                WeatherIcon::Sleet { is_night },
+                WeatherIcon::Snow { is_night },
+                WeatherIcon::Thunder { is_night },
+                WeatherIcon::Default,
+            ] {
+                let name = icon.to_icon_str();
+                assert!(default_icons.get(name, None).is_some(), "{name}");
+                for icon_set in icon_sets {
+                    let defined = icon_set
+                        .lines()
+                        .any(|line| line.split('=').next().map(str::trim) == Some(name));
+                    assert!(defined, "{name}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut text = String::from("Sunny, with a high near 75.");
+        truncate(&mut text, 30);
+        assert_eq!(text, "Sunny, with a high near 75.");
+        truncate(&mut text, 10);
+        assert_eq!(text, "Sunny, wi…");
+        assert_eq!(text.chars().count(), 10);
+
+        // Combining marks stay with their letter
+        let mut text = String::from("Mayagu\u{308}ez, PR");
+        truncate(&mut text, 7);
+        assert_eq!(text, "Mayagu\u{308}…");
+    }
+
+    #[tokio::test]
+    async fn test_http_client_proxy() {
+        use std::io::{Read, Write as _};
+
+        assert!(http_client(Some("not a url")).is_err());
+
+        // The host does not exist, so the request only succeeds if it goes through the proxy
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let len = stream.read(&mut buf).unwrap();
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            String::from_utf8_lossy(&buf[..len]).into_owned()
+        });
+        let client = http_client(Some(&proxy)).unwrap();
+        let response = client.get("http://weather.invalid/forecast").send().await;
+        assert!(response.unwrap().status().is_success());
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET http://weather.invalid/forecast "));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let interval = Duration::from_secs(600);
+        let mut jitter = Jitter::new(Duration::from_secs(5));
+        for _ in 0..100 {
+            let jittered = jitter.apply(interval);
+            assert!(jittered >= Duration::from_secs(595));
+            assert!(jittered <= Duration::from_secs(605));
+        }
+        assert_eq!(Jitter::new(Duration::ZERO).apply(interval), interval);
+    }
+
+    fn ramp(temps: &[f64]) -> Vec<ForecastAggregate> {
+        temps
+            .iter()
+            .map(|&temp| ForecastAggregate {
+                temp,
+                apparent: temp,
+                humidity: 50.0,
+                dewpoint: None,
+                pressure: None,
+                visibility: None,
+                wind: 10.0,
+                wind_kmh: 10.0,
+                wind_mph: 10.0 / MPH_TO_KPH,
+                wind_direction: Some(90.0),
+                wind_gust: None,
+                wind_gust_kmh: None,
+                precip_prob: None,
+                start: None,
+            })
+            .collect()
+    }
+
+    fn moment() -> WeatherMoment {
+        WeatherMoment {
+            icon: WeatherIcon::Default,
+            weather: String::new(),
+            weather_verbose: String::new(),
+            weather_detailed: None,
+            temp: 0.0,
+            apparent: 0.0,
+            humidity: 0.0,
+            dewpoint: None,
+            pressure: None,
+            visibility: None,
+            wind: 0.0,
+            wind_kmh: 0.0,
+            wind_mph: 0.0,
+            wind_direction: None,
+            wind_gust: None,
+            wind_gust_kmh: None,
+            precip_prob: None,
+        }
+    }
+
+    #[test]
+    fn test_final_period() {
+        assert_eq!(FinalPeriod::Horizon.index(0, 3, 10), Some(3));
+        assert_eq!(FinalPeriod::Horizon.index(2, 3, 4), Some(3));
+        assert_eq!(FinalPeriod::LastAggregated.index(0, 3, 10), Some(2));
+        assert_eq!(FinalPeriod::LastAggregated.index(2, 3, 10), Some(4));
+        assert_eq!(FinalPeriod::LastAggregated.index(0, 0, 1), Some(0));
+        assert_eq!(FinalPeriod::Omitted.index(0, 3, 10), None);
+        let final_period: FinalPeriod = serde_json::from_str("\"none\"").unwrap();
+        assert_eq!(final_period, FinalPeriod::Omitted);
+    }
+
+    #[test]
+    fn test_weighting() {
+        let data = ramp(&[10.0, 20.0, 30.0, 40.0]);
+        let uniform = combine_forecasts(
+            &data,
+            Some(moment()),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        );
+        assert_eq!(uniform.avg.temp, 25.0);
+        assert_eq!(uniform.count, 4);
+
+        // Weights 4, 3, 2, 1
+        let decay = combine_forecasts(
+            &data,
+            Some(moment()),
+            Aggregate::Mean,
+            Weighting::LinearDecay,
+            false,
+            Extrema::PerField,
+        );
+        assert_eq!(decay.avg.temp, 20.0);
+        assert!((decay.avg.wind_kmh - 10.0).abs() < 1e-9);
+        assert!((decay.avg.wind_direction.unwrap() - 90.0).abs() < 1e-9);
+        assert_eq!(decay.min.temp, 10.0);
+        assert_eq!(decay.max.temp, 40.0);
+    }
+
+    #[test]
+    fn test_icon_strip() {
+        let period = |icon| ForecastPeriod {
+            start: None,
+            weather: WeatherMoment { icon, ..moment() },
+        };
+        let data = WeatherResult {
+            location: String::new(),
+            location_distance: None,
+            location_bearing: None,
+            current_weather: moment(),
+            forecast: None,
+            alerts: None,
+            updated: None,
+            periods: vec![
+                period(WeatherIcon::Clear { is_night: false }),
+                period(WeatherIcon::Clouds { is_night: false }),
+                period(WeatherIcon::Rain { is_night: true }),
+            ],
+            coordinates: None,
+            temp_unit: TempUnit::Celsius,
+            pressure_unit: PressureUnit::Hpa,
+            units: UnitSystem::Metric,
+            provider: "",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
+        };
+        assert_eq!(data.icon_strip(6), "☀☁🌧");
+        assert_eq!(data.icon_strip(2), "☀☁");
+        assert_eq!(data.icon_strip(0), "");
+    }
+
+    #[test]
+    fn test_temp_trend() {
+        let at = |temp| WeatherMoment { temp, ..moment() };
+        let mut data = WeatherResult {
+            location: String::new(),
+            location_distance: None,
+            location_bearing: None,
+            current_weather: at(10.0),
+            forecast: None,
+            alerts: None,
+            updated: None,
+            periods: Vec::new(),
+            coordinates: None,
+            temp_unit: TempUnit::Celsius,
+            pressure_unit: PressureUnit::Hpa,
+            units: UnitSystem::Metric,
+            provider: "",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
+        };
+        assert_eq!(data.temp_trend(None, 1.0), None);
+
+        let data_agg = ramp(&[10.0]);
+        let forecast = combine_forecasts(
+            &data_agg,
+            Some(at(12.0)),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        );
+        data.forecast = Some(forecast);
+        assert_eq!(data.temp_trend(None, 1.0), Some("↑"));
+        assert_eq!(data.temp_trend(Some(3), 1.0), Some("↑"));
+        assert_eq!(data.temp_trend(None, 5.0), Some("→"));
+
+        data.periods = [11.0, 9.0]
+            .map(|temp| ForecastPeriod {
+                start: None,
+                weather: at(temp),
+            })
+            .into();
+        assert_eq!(data.temp_trend(Some(1), 1.0), Some("↑"));
+        assert_eq!(data.temp_trend(Some(2), 1.0), Some("↓"));
+        // Fewer periods than the horizon
+        assert_eq!(data.temp_trend(Some(12), 1.0), Some("↓"));
+    }
+
+    #[test]
+    fn test_sun_times() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        // Boston, where the sun rises at 09:07 UTC and sets at 00:25 UTC on the solstice
+        let Daylight::Times { sunrise, sunset } = sun_times(42.36, -71.06, date) else {
+            panic!("no sunrise in Boston");
+        };
+        let expected_sunrise = "2024-06-21T09:07:00Z".parse::<DateTime<Utc>>().unwrap();
+        let expected_sunset = "2024-06-22T00:25:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert!((sunrise - expected_sunrise).num_minutes().abs() <= 2, "{sunrise}");
+        assert!((sunset - expected_sunset).num_minutes().abs() <= 2, "{sunset}");
+        let hour = chrono::Duration::try_hours(1).unwrap();
+        let boston = sun_times(42.36, -71.06, date);
+        assert!(boston.is_daylight(sunrise + hour));
+        assert!(!boston.is_daylight(sunrise - hour));
+        assert!(!boston.is_daylight(sunset + hour));
+
+        assert_eq!(sun_times(78.22, 15.65, date), Daylight::PolarDay);
+        assert_eq!(sun_times(-78.22, 15.65, date), Daylight::PolarNight);
+    }
+
+    #[test]
+    fn test_moon_phase() {
+        let moon = |y, m, d| moon_phase(chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap());
+        // Full moon on 2024-06-22 01:08 UTC, new moon on 2024-07-05 22:57 UTC
+        let full = moon(2024, 6, 22);
+        assert_eq!((full.name, full.icon), ("Full Moon", "🌕"));
+        assert!((full.fraction - 0.5).abs() < 0.05, "{}", full.fraction);
+        assert_eq!(moon(2024, 7, 6).name, "New Moon");
+        assert_eq!(moon(2024, 6, 14).name, "First Quarter");
+        assert_eq!(moon(2024, 6, 28).name, "Last Quarter");
+        assert_eq!(moon(2024, 6, 10).name, "Waxing Crescent");
+        assert_eq!(moon(2024, 6, 25).name, "Waning Gibbous");
+    }
 }
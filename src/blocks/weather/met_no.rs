@@ -13,15 +13,23 @@ pub struct Config {
     forecast_hours: usize,
 }
 
+impl Config {
+    pub(super) fn has_coordinates(&self) -> bool {
+        self.coordinates.is_some()
+    }
+}
+
 pub(super) struct Service<'a> {
     config: &'a Config,
+    client: reqwest::Client,
     legend: &'static LegendsStore,
 }
 
 impl<'a> Service<'a> {
-    pub(super) fn new(config: &'a Config) -> Result<Service<'a>> {
+    pub(super) fn new(config: &'a Config, client: reqwest::Client) -> Result<Service<'a>> {
         Ok(Self {
             config,
+            client,
             legend: LEGENDS.as_ref().error("Invalid legends file")?,
         })
     }
@@ -54,11 +62,19 @@ impl<'a> Service<'a> {
             temp,
             apparent: australian_apparent_temp(temp, humidity, wind_speed),
             humidity,
+            dewpoint: None,
+            pressure: None,
+            visibility: None,
             weather: translated.clone(),
             weather_verbose: translated,
-            wind: wind_speed,
+            weather_detailed: None,
+            wind: wind_speed * 3.6,
             wind_kmh: wind_speed * 3.6,
+            wind_mph: wind_speed * 3.6 / MPH_TO_KPH,
             wind_direction: instant.wind_from_direction,
+            wind_gust: None,
+            wind_gust_kmh: None,
+            precip_prob: None,
             icon: weather_to_icon(summary, is_night),
         }
     }
@@ -159,13 +175,15 @@ impl WeatherProvider for Service<'_> {
             .or_else(|| self.config.coordinates.clone())
             .error("No location given")?;
 
+        let coordinates = parse_coordinates(&lat, &lon);
         let querystr: HashMap<&str, String> = map! {
             "lat" => lat,
             "lon" => lon,
             [if let Some(alt) = &self.config.altitude] "altitude" => alt,
         };
 
-        let data: ForecastResponse = REQWEST_CLIENT
+        let data: ForecastResponse = self
+            .client
             .get(FORECAST_URL)
             .query(&querystr)
             .header(reqwest::header::CONTENT_TYPE, "application/json")
@@ -259,42 +277,84 @@ impl WeatherProvider for Service<'_> {
                     temp: temp_avg,
                     apparent: apparent_avg,
                     humidity: humidity_avg,
-                    wind: wind_avg,
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: wind_avg * 3.6,
                     wind_kmh: wind_avg * 3.6,
+                    wind_mph: wind_avg * 3.6 / MPH_TO_KPH,
                     wind_direction: direction_avg,
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                    start: None,
                 },
                 min: ForecastAggregate {
                     temp: temp_min,
                     apparent: apparent_min,
                     humidity: humidity_min,
-                    wind: *wind_min,
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: wind_min * 3.6,
                     wind_kmh: wind_min * 3.6,
+                    wind_mph: wind_min * 3.6 / MPH_TO_KPH,
                     wind_direction: *direction_min,
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                    start: None,
                 },
                 max: ForecastAggregate {
                     temp: temp_max,
                     apparent: apparent_max,
                     humidity: humidity_max,
-                    wind: *wind_max,
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: wind_max * 3.6,
                     wind_kmh: wind_max * 3.6,
+                    wind_mph: wind_max * 3.6 / MPH_TO_KPH,
                     wind_direction: *direction_max,
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                    start: None,
                 },
-                fin: self.get_weather_instant(&data.properties.timeseries[forecast_hours - 1].data),
+                fin: Some(
+                    self.get_weather_instant(&data.properties.timeseries[forecast_hours - 1].data),
+                ),
+                count: forecast_hours,
+                precip_any: None,
             })
         };
 
         Ok(WeatherResult {
             location: location.map_or("Unknown".to_string(), |c| c.city.clone()),
+            location_distance: None,
+            location_bearing: None,
             current_weather: self
                 .get_weather_instant(&data.properties.timeseries.first().unwrap().data),
             forecast,
+            alerts: None,
+            updated: None,
+            periods: Vec::new(),
+            coordinates,
+            temp_unit: TempUnit::Celsius,
+            pressure_unit: PressureUnit::Hpa,
+            units: UnitSystem::Metric,
+            provider: "metno",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
         })
     }
 }
 
 fn weather_to_icon(weather: &str, is_night: bool) -> WeatherIcon {
     match weather {
-        "cloudy" | "partlycloudy" | "fair" => WeatherIcon::Clouds{is_night},
+        "cloudy" => WeatherIcon::Clouds{is_night},
+        "partlycloudy" | "fair" => WeatherIcon::PartlyCloudy{is_night},
         "fog" => WeatherIcon::Fog{is_night},
         "clearsky" => WeatherIcon::Clear{is_night},
         "heavyrain" | "heavyrainshowers" | "lightrain" | "lightrainshowers" | "rain"
@@ -319,9 +379,10 @@ fn weather_to_icon(weather: &str, is_night: bool) -> WeatherIcon {
         | "lightssnowshowersandthunder"// There's a typo in the api it will be fixed in the next version to the following entry
         | "lightsnowshowersandthunder"
         | "lightrainshowersandthunder" => WeatherIcon::Thunder{is_night},
-        "heavysleet" | "heavysleetshowers" | "heavysnow" | "heavysnowshowers" | "lightsleet"
-        | "lightsleetshowers" | "lightsnow" | "lightsnowshowers" | "sleet" | "sleetshowers"
-        | "snow" | "snowshowers" => WeatherIcon::Snow,
+        "heavysleet" | "heavysleetshowers" | "lightsleet" | "lightsleetshowers" | "sleet"
+        | "sleetshowers" => WeatherIcon::Sleet{is_night},
+        "heavysnow" | "heavysnowshowers" | "lightsnow" | "lightsnowshowers" | "snow"
+        | "snowshowers" => WeatherIcon::Snow{is_night},
         _ => WeatherIcon::Default,
     }
 }
@@ -3,374 +3,1783 @@
 //! The API is documented [here](https://www.weather.gov/documentation/services-web-api).
 //! There is a corresponding [OpenAPI document](https://api.weather.gov/openapi.json). The forecast
 //! descriptions are translated into the set of supported icons as best as possible, and a more
-//! complete summary forecast is available in the `weather_verbose` format key. The full NWS list
+//! complete summary forecast is available in the `weather_verbose` format key. Daily forecasts
+//! also provide a paragraph of text in the `weather_detailed` key. The full NWS list
 //! of icons and corresponding descriptions can be found [here](https://api.weather.gov/icons),
 //! though these are slated for deprecation.
 //!
 //! All data is gathered using the hourly weather forecast service, after resolving from latitude &
-//! longitude coordinates to a specific forecast office and grid point.
+//! longitude coordinates to a specific forecast office and grid point. The result of that
+//! resolution is cached on disk, since the grid point for a given location practically never
+//! changes.
 //!
 
 use super::*;
-use serde::Deserialize;
+use backon::ConstantBuilder;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 const API_URL: &str = "https://api.weather.gov/";
 
-const MPH_TO_KPH: f64 = 1.609344;
+const DEFAULT_USER_AGENT: &str = concat!(
+    "i3status-rust/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/greshake/i3status-rust)"
+);
+
+const KNOT_TO_MS: f64 = 1852.0 / 3600.0;
+
+/// Flags asking for the forecast values with their unit codes
+const DEFAULT_FEATURE_FLAGS: &[&str] = &["forecast_wind_speed_qv", "forecast_temperature_qv"];
+
+/// Relative humidity assumed for forecast periods that don't report one
+const NEUTRAL_HUMIDITY: f64 = 50.0;
+
+/// Shared by all NWS blocks, so that together they stay under `max_requests_per_minute`
+static RATE_LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter {
+    tokens: 0.0,
+    updated: None,
+});
 
 #[derive(Deserialize, Debug, SmartDefault)]
 #[serde(tag = "name", rename_all = "lowercase", deny_unknown_fields, default)]
 pub struct Config {
+    #[serde(deserialize_with = "deserialize_optional_coordinates")]
     coordinates: Option<(String, String)>,
+    #[serde(deserialize_with = "deserialize_locations")]
+    locations: Vec<(String, String)>,
+    #[serde(deserialize_with = "deserialize_optional_coordinates")]
+    fallback_coordinates: Option<(String, String)>,
+    #[serde(deserialize_with = "deserialize_station")]
+    station: Option<String>,
+    office: Option<String>,
+    grid_x: Option<u32>,
+    grid_y: Option<u32>,
     #[default(12)]
+    #[serde(deserialize_with = "deserialize_forecast_hours")]
     forecast_hours: usize,
+    forecast_offset: usize,
+    #[serde(default)]
+    location_part: LocationPart,
+    max_location_len: Option<NonZeroUsize>,
     #[serde(default)]
     units: UnitSystem,
+    temp_unit: Option<TempUnit>,
+    pressure_unit: Option<PressureUnit>,
+    #[default(Seconds::new(60 * 60 * 24 * 30))]
+    location_cache_ttl: Seconds<false>,
+    show_alerts: bool,
+    show_daily_summary: bool,
+    #[serde(default)]
+    forecast_mode: ForecastMode,
+    #[serde(default)]
+    current_period: CurrentPeriod,
+    #[serde(default)]
+    aggregate: Aggregate,
+    #[serde(default)]
+    aggregate_weighting: Weighting,
+    #[serde(default)]
+    final_period: FinalPeriod,
+    #[serde(default)]
+    extrema_mode: Extrema,
+    #[serde(default)]
+    apparent_temp: ApparentTemp,
+    #[default(true)]
+    apparent: bool,
+    couple_apparent: bool,
+    calm_below: Option<f64>,
+    use_observations: bool,
+    use_api_icon: bool,
+    #[default(3)]
+    retries: usize,
+    #[default(Seconds::new(1))]
+    retry_delay: Seconds<false>,
+    #[default(2)]
+    connect_retries: usize,
+    #[default(Seconds::new(2))]
+    connect_retry_delay: Seconds<false>,
+    #[default(Seconds::new(10))]
+    timeout: Seconds<false>,
+    max_requests_per_minute: Option<NonZeroU32>,
+    #[default(DEFAULT_FEATURE_FLAGS.iter().map(|&flag| flag.into()).collect())]
+    #[serde(deserialize_with = "deserialize_feature_flags")]
+    feature_flags: Vec<String>,
+    dry_run: bool,
+    #[default(DEFAULT_USER_AGENT.into())]
+    user_agent: String,
+    #[serde(deserialize_with = "deserialize_api_url")]
+    api_url: Option<String>,
 }
 
-#[derive(Clone, Debug)]
-struct LocationInfo {
-    query: String,
-    name: String,
+/// The current weather is always shown, so at least one hour has to be forecast
+fn deserialize_forecast_hours<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match usize::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("'forecast_hours' must be at least 1")),
+        hours => Ok(hours),
+    }
 }
 
-pub(super) struct Service<'a> {
-    config: &'a Config,
-    location: Option<LocationInfo>,
+/// Latitude and longitude, either as a pair or as a single string separated by a comma or a space
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CoordinatesConfig {
+    Pair(String, String),
+    Joined(String),
 }
 
-impl<'a> Service<'a> {
-    pub(super) async fn new(autolocate: bool, config: &'a Config) -> Result<Service<'a>> {
-        let location = if autolocate {
-            None
-        } else {
-            let coords = config.coordinates.as_ref().error("no location given")?;
-            Some(Self::get_location_query(&coords.0, &coords.1, config.units).await?)
+struct ValidCoordinates((String, String));
+
+impl<'de> Deserialize<'de> for ValidCoordinates {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (lat, lon) = match CoordinatesConfig::deserialize(deserializer)? {
+            CoordinatesConfig::Pair(lat, lon) => (lat, lon),
+            CoordinatesConfig::Joined(joined) => {
+                let parts: Vec<&str> = joined
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|part| !part.is_empty())
+                    .collect();
+                match parts[..] {
+                    [lat, lon] => (lat.into(), lon.into()),
+                    _ => {
+                        return Err(de::Error::custom(format!(
+                            "'{joined}' is not a latitude and longitude such as \"42.36,-71.06\""
+                        )))
+                    }
+                }
+            }
         };
-        Ok(Self { config, location })
+        validate_coordinates(lat.trim(), lon.trim())
+            .map(|()| Self((lat.trim().into(), lon.trim().into())))
+            .map_err(de::Error::custom)
     }
+}
 
-    async fn get_location_query(lat: &str, lon: &str, units: UnitSystem) -> Result<LocationInfo> {
-        let points_url = format!("{API_URL}/points/{lat},{lon}");
-
-        let response: ApiPoints = REQWEST_CLIENT
-            .get(points_url)
-            .send()
-            .await
-            .error("Zone resolution request failed")?
-            .json()
-            .await
-            .error("Failed to parse zone resolution request")?;
-        let mut query = response.properties.forecast_hourly;
-        query.push_str(match units {
-            UnitSystem::Metric => "?units=si",
-            UnitSystem::Imperial => "?units=us",
-        });
-        let location = response.properties.relative_location.properties;
-        let name = format!("{}, {}", location.city, location.state);
-        Ok(LocationInfo { query, name })
+/// Check that the coordinates are numbers within range, so that typos don't end up in requests
+fn validate_coordinates(lat: &str, lon: &str) -> Result<(), String> {
+    let parse = |value: &str, name| match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(format!("'{value}' is not a valid {name}")),
+    };
+    let (lat_value, lon_value) = (parse(lat, "latitude")?, parse(lon, "longitude")?);
+    if !(-90.0..=90.0).contains(&lat_value) {
+        let hint = if (-90.0..=90.0).contains(&lon_value) {
+            " (are the latitude and longitude swapped?)"
+        } else {
+            ""
+        };
+        return Err(format!("latitude {lat} is not between -90 and 90{hint}"));
+    }
+    if !(-180.0..=180.0).contains(&lon_value) {
+        return Err(format!("longitude {lon} is not between -180 and 180"));
     }
+    Ok(())
 }
 
-#[derive(Deserialize, Debug)]
-struct ApiPoints {
-    properties: ApiPointsProperties,
+fn deserialize_optional_coordinates<'de, D>(
+    deserializer: D,
+) -> Result<Option<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Some(ValidCoordinates::deserialize(deserializer)?.0))
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct ApiPointsProperties {
-    forecast_hourly: String,
-    relative_location: ApiRelativeLocation,
+fn deserialize_locations<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let locations = Vec::<ValidCoordinates>::deserialize(deserializer)?;
+    Ok(locations.into_iter().map(|location| location.0).collect())
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct ApiRelativeLocation {
-    properties: ApiRelativeLocationProperties,
+/// Station identifiers are short alphanumeric codes: ICAO codes such as `KBOS`, and longer ones
+/// for other networks, such as `LOXC1`
+fn deserialize_station<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let station = String::deserialize(deserializer)?.trim().to_uppercase();
+    let valid = (1..=5).contains(&station.len())
+        && station.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid {
+        return Err(de::Error::custom(format!(
+            "'{station}' is not a valid station identifier, expected up to 5 letters and digits \
+             such as \"KBOS\""
+        )));
+    }
+    Ok(Some(station))
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct ApiRelativeLocationProperties {
-    city: String,
-    state: String,
+/// Feature flags are sent in a comma separated header, so they have to be simple tokens
+fn deserialize_feature_flags<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let flags = Vec::<String>::deserialize(deserializer)?;
+    for flag in &flags {
+        let valid = !flag.is_empty()
+            && flag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(de::Error::custom(format!(
+                "'{flag}' is not a valid feature flag"
+            )));
+        }
+    }
+    Ok(flags)
 }
 
-#[derive(Deserialize, Debug)]
-struct ApiForecastResponse {
-    properties: ApiForecastProperties,
+/// Make sure the URL can be used as a base for the API endpoints
+fn deserialize_api_url<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut url = String::deserialize(deserializer)?;
+    let parsed = reqwest::Url::parse(&url).map_err(de::Error::custom)?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(de::Error::custom("'api_url' must be an http or https URL"));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(de::Error::custom(
+            "'api_url' must not have a query or a fragment",
+        ));
+    }
+    if !url.ends_with('/') {
+        url.push('/');
+    }
+    Ok(Some(url))
 }
 
-#[derive(Deserialize, Debug)]
-struct ApiForecastProperties {
-    periods: Vec<ApiForecast>,
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "lowercase")]
+enum ForecastMode {
+    /// One period per hour
+    #[default]
+    Hourly,
+    /// One period per day or night, each covering about 12 hours
+    Daily,
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct ApiValue {
-    value: f64,
-    unit_code: String,
+/// Which part of the location name is shown
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "lowercase")]
+enum LocationPart {
+    /// City and state, e.g. "Boston, MA"
+    #[default]
+    Full,
+    City,
+    State,
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct ApiForecast {
-    is_daytime: bool,
-    temperature: ApiValue,
-    relative_humidity: ApiValue,
-    wind_speed: ApiValue,
-    wind_direction: String,
-    short_forecast: String,
+/// Which forecast period the current weather is taken from
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, SmartDefault)]
+#[serde(rename_all = "snake_case")]
+enum CurrentPeriod {
+    /// The first period received, which may have started up to an hour ago
+    #[default]
+    First,
+    /// The period containing the current time, or else the one starting closest to it
+    NearestToNow,
 }
 
-impl ApiForecast {
-    fn wind_direction(&self) -> f64 {
-        let dir = match self.wind_direction.as_str() {
-            "NNE" => 1,
-            "NE" => 2,
-            "ENE" => 3,
-            "E" => 4,
-            "ESE" => 5,
-            "SE" => 6,
-            "SSE" => 7,
-            "S" => 8,
-            "SSW" => 9,
-            "SW" => 10,
-            "WSW" => 11,
-            "W" => 12,
-            "WNW" => 13,
-            "NW" => 14,
-            "NNW" => 15,
-            _ => 0,
+impl CurrentPeriod {
+    /// Index of the current period in `periods`
+    fn index(self, periods: &[ApiForecast], now: DateTime<Utc>) -> usize {
+        if self == Self::First {
+            return 0;
+        }
+        let time = |time: &Option<String>| time.as_deref().and_then(parse_time);
+        let containing = periods.iter().position(|period| {
+            matches!(
+                (time(&period.start_time), time(&period.end_time)),
+                (Some(start), Some(end)) if start <= now && now < end
+            )
+        });
+        containing
+            .or_else(|| {
+                let distance = |period: &ApiForecast| {
+                    time(&period.start_time).map(|start| (start - now).num_seconds().abs())
+                };
+                (0..periods.len()).min_by_key(|&i| distance(&periods[i]).unwrap_or(i64::MAX))
+            })
+            .unwrap_or(0)
+    }
+}
+
+impl Config {
+    pub(super) fn has_coordinates(&self) -> bool {
+        self.coordinates.is_some() || !self.locations.is_empty()
+    }
+
+    /// The wind direction, unless `wind`, in the units of the unit system, is below `calm_below`
+    fn calm_direction(&self, wind: f64, direction: Option<f64>) -> Option<f64> {
+        match self.calm_below {
+            Some(calm_below) if wind < calm_below => None,
+            _ => direction,
+        }
+    }
+
+    /// The location name as configured to be shown. The full name is kept in the cache, so that
+    /// changing these options doesn't require resolving the location again.
+    fn location_name(&self, name: &str) -> String {
+        // Names of grid points have no state
+        let name = match (self.location_part, name.rsplit_once(", ")) {
+            (LocationPart::City, Some((city, _))) => city,
+            (LocationPart::State, Some((_, state))) => state,
+            _ => name,
         };
-        (dir as f64) * (360.0 / 16.0)
+        let mut name = name.to_string();
+        if let Some(max) = self.max_location_len {
+            truncate(&mut name, max.get());
+        }
+        name
+    }
+
+    /// Base URL of the API, always ending with a `/`
+    fn api_url(&self) -> &str {
+        self.api_url.as_deref().unwrap_or(API_URL)
+    }
+
+    fn points_url(&self, lat: &str, lon: &str) -> String {
+        format!(
+            "{}points/{},{}",
+            self.api_url(),
+            round_coordinate(lat),
+            round_coordinate(lon)
+        )
     }
 
-    fn icon_to_word(icon: WeatherIcon) -> String {
-        match icon {
-            WeatherIcon::Clear { .. } => "Clear",
-            WeatherIcon::Clouds { .. } => "Clouds",
-            WeatherIcon::Fog { .. } => "Fog",
-            WeatherIcon::Thunder { .. } => "Thunder",
-            WeatherIcon::Rain { .. } => "Rain",
-            WeatherIcon::Snow => "Snow",
-            WeatherIcon::Default => "Unknown",
+    /// URL of the forecast for the given grid point, in the configured mode
+    ///
+    /// No units are requested: the API doesn't always honor them, so every value is converted
+    /// according to its own unit code instead.
+    fn gridpoint_forecast_url(&self, office: &str, x: u32, y: u32) -> String {
+        let mode = match self.forecast_mode {
+            ForecastMode::Hourly => "/hourly",
+            ForecastMode::Daily => "",
+        };
+        format!(
+            "{}gridpoints/{office}/{x},{y}/forecast{mode}",
+            self.api_url()
+        )
+    }
+
+    fn gridpoint_stations_url(&self, office: &str, x: u32, y: u32) -> String {
+        format!("{}gridpoints/{office}/{x},{y}/stations", self.api_url())
+    }
+
+    fn station_url(&self, station: &str) -> String {
+        format!("{}stations/{station}", self.api_url())
+    }
+
+    fn observation_url(&self, station: &str) -> String {
+        format!("{}stations/{station}/observations/latest", self.api_url())
+    }
+
+    /// Point an absolute URL returned by the API to the configured server
+    fn rebase_url(&self, url: String) -> String {
+        match url.strip_prefix(API_URL) {
+            Some(path) => format!("{}{path}", self.api_url()),
+            None => url,
         }
-        .to_string()
     }
 
-    fn wind_kmh(&self) -> f64 {
-        if self.wind_speed.unit_code.ends_with("km_h-1") {
-            self.wind_speed.value
+    fn alerts_url(&self, point: &str) -> String {
+        format!("{}alerts/active?point={point}", self.api_url())
+    }
+
+    /// The apparent temperature in °C, or the plain temperature if it is disabled
+    fn apparent_celsius(&self, temp: f64, humidity: f64, wind_kmh: f64) -> f64 {
+        if self.apparent {
+            self.apparent_temp.compute(temp, humidity, wind_kmh)
         } else {
-            self.wind_speed.value * MPH_TO_KPH
+            temp
         }
     }
 
-    fn apparent_temp(&self) -> f64 {
-        let temp = if self.temperature.unit_code.ends_with("degC") {
-            self.temperature.value
-        } else {
-            (self.temperature.value - 32.0) * 5.0 / 9.0
-        };
-        let humidity = self.relative_humidity.value;
-        let wind_speed = self.wind_kmh();
-        australian_apparent_temp(temp, humidity, wind_speed)
+    /// The unit temperatures are shown in, by default the one of the unit system
+    fn temp_unit(&self) -> TempUnit {
+        self.temp_unit.unwrap_or(self.units.temp_unit())
     }
 
-    fn to_moment(&self) -> WeatherMoment {
-        let icon = short_forecast_to_icon(&self.short_forecast, !self.is_daytime);
-        let weather = Self::icon_to_word(icon);
-        WeatherMoment {
-            icon,
-            weather,
-            weather_verbose: self.short_forecast.clone(),
-            temp: self.temperature.value,
-            apparent: self.apparent_temp(),
-            humidity: self.relative_humidity.value,
-            wind: self.wind_speed.value,
-            wind_kmh: self.wind_kmh(),
-            wind_direction: Some(self.wind_direction()),
+    /// The unit pressures are shown in, by default the one of the unit system
+    fn pressure_unit(&self) -> PressureUnit {
+        self.pressure_unit.unwrap_or(self.units.pressure_unit())
+    }
+
+    /// Number of forecast periods covering `forecast_hours`
+    fn forecast_periods(&self) -> usize {
+        match self.forecast_mode {
+            ForecastMode::Hourly => self.forecast_hours,
+            ForecastMode::Daily => self.forecast_hours.div_ceil(12),
         }
     }
+}
 
-    fn to_aggregate(&self) -> ForecastAggregate {
-        ForecastAggregate {
-            temp: self.temperature.value,
-            apparent: self.apparent_temp(),
-            humidity: self.relative_humidity.value,
-            wind: self.wind_speed.value,
-            wind_kmh: self.wind_kmh(),
-            wind_direction: Some(self.wind_direction()),
-        }
-    }
-}
-
-fn combine_forecasts(data: &[ForecastAggregate], fin: WeatherMoment) -> Forecast {
-    let mut temp = 0.0;
-    let mut apparent = 0.0;
-    let mut humidity = 0.0;
-    let mut wind_north = 0.0;
-    let mut wind_east = 0.0;
-    let mut wind_kmh_north = 0.0;
-    let mut wind_kmh_east = 0.0;
-    let mut max = ForecastAggregate {
-        temp: -1000.0,
-        apparent: -1000.0,
-        humidity: 0.0,
-        wind: 0.0,
-        wind_kmh: 0.0,
-        wind_direction: Some(0.0),
-    };
-    let mut min = ForecastAggregate {
-        temp: 1000.0,
-        apparent: 1000.0,
-        humidity: 100.0,
-        wind: 1000.0,
-        wind_kmh: 1000.0,
-        wind_direction: Some(0.0),
-    };
-    for val in data {
-        // Summations for averaging
-        temp += val.temp;
-        apparent += val.apparent;
-        humidity += val.humidity;
-        let (sin, cos) = val
-            .wind_direction
-            .unwrap_or_default()
-            .to_radians()
-            .sin_cos();
-        wind_north += val.wind * cos;
-        wind_east += val.wind * sin;
-        wind_kmh_north += val.wind_kmh * cos;
-        wind_kmh_east += val.wind_kmh * sin;
-
-        // Max
-        max.temp = max.temp.max(val.temp);
-        max.apparent = max.apparent.max(val.apparent);
-        max.humidity = max.humidity.max(val.humidity);
-        if val.wind > max.wind {
-            max.wind_direction = val.wind_direction;
-            max.wind = val.wind;
-            max.wind_kmh = val.wind_kmh;
-        }
-
-        // Min
-        min.temp = min.temp.min(val.temp);
-        min.apparent = min.apparent.min(val.apparent);
-        min.humidity = min.humidity.min(val.humidity);
-        if val.wind < min.wind {
-            min.wind_direction = val.wind_direction;
-            min.wind = val.wind;
-            min.wind_kmh = val.wind_kmh;
-        }
-    }
-
-    let count = data.len() as f64;
-    let avg = ForecastAggregate {
-        temp: temp / count,
-        apparent: apparent / count,
-        humidity: humidity / count,
-        wind: wind_east.hypot(wind_north) / count,
-        wind_kmh: wind_kmh_east.hypot(wind_kmh_north) / count,
-        wind_direction: Some(wind_east.atan2(wind_north).to_degrees().rem_euclid(360.0)),
-    };
-    Forecast { avg, min, max, fin }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LocationInfo {
+    query: String,
+    name: String,
+    /// Distance from the named location, in meters
+    #[serde(default)]
+    distance: Option<f64>,
+    /// Bearing from the named location, in degrees
+    #[serde(default)]
+    bearing: Option<f64>,
+    /// The `lat,lon` pair the location was resolved from
+    point: String,
+    /// Identifier of the nearest observation station, if `use_observations` is set
+    #[serde(default)]
+    station: Option<String>,
+    /// Missing from locations cached by older versions
+    #[serde(default)]
+    grid_point: Option<GridPoint>,
 }
 
-#[async_trait]
-impl WeatherProvider for Service<'_> {
-    async fn get_weather(
-        &self,
-        autolocated: Option<&Coordinates>,
-        need_forecast: bool,
-    ) -> Result<WeatherResult> {
-        let location = if let Some(coords) = autolocated {
-            Self::get_location_query(
-                &coords.latitude.to_string(),
-                &coords.longitude.to_string(),
-                self.config.units,
-            )
-            .await?
+pub(super) struct Service<'a> {
+    config: &'a Config,
+    client: reqwest::Client,
+    locations: Vec<LocationInfo>,
+    /// Index of the location to show next
+    current: AtomicUsize,
+    /// Location of `fallback_coordinates`, resolved the first time it is needed
+    fallback: Mutex<Option<LocationInfo>>,
+    /// The last autolocated location, reused while the coordinates stay the same
+    autolocated: Mutex<Option<LocationInfo>>,
+    stats: Stats,
+}
+
+/// Counters of the requests made by the block, shown by the `debug_stats` key
+#[derive(Default, Debug)]
+struct Stats {
+    /// Requests sent, including retries
+    requests: AtomicU64,
+    /// Requests that failed after all retries
+    failures: AtomicU64,
+    /// Locations loaded from the cache on disk
+    cache_hits: AtomicU64,
+    /// Locations that had to be resolved because they were missing from the cache on disk
+    cache_misses: AtomicU64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        write!(
+            f,
+            "{} requests, {} failed, {}/{} locations cached",
+            load(&self.requests),
+            load(&self.failures),
+            load(&self.cache_hits),
+            load(&self.cache_hits) + load(&self.cache_misses),
+        )
+    }
+}
+
+impl<'a> Service<'a> {
+    pub(super) async fn new(
+        autolocate: bool,
+        config: &'a Config,
+        client: reqwest::Client,
+    ) -> Result<Service<'a>> {
+        if !autolocate && config.fallback_coordinates.is_some() {
+            return Err(Error::new("fallback_coordinates requires autolocate"));
+        }
+        let stats = Stats::default();
+        let mut locations = Vec::new();
+        if let Some(location) = Self::station_location(autolocate, config, &client, &stats).await?
+        {
+            locations.push(location);
+        } else if let Some(location) =
+            Self::gridpoint_location(autolocate, config, &client, &stats).await?
+        {
+            locations.push(location);
+        } else if !autolocate {
+            for (lat, lon) in config.coordinates.iter().chain(&config.locations) {
+                locations.push(Self::get_location_query(lat, lon, config, &client, &stats).await?);
+            }
+            if locations.is_empty() {
+                return Err(Error::new("no location given"));
+            }
+        }
+        Ok(Self {
+            config,
+            client,
+            locations,
+            current: AtomicUsize::new(0),
+            fallback: Mutex::new(None),
+            autolocated: Mutex::new(None),
+            stats,
+        })
+    }
+
+    /// The location of `fallback_coordinates`, for when autolocation fails
+    async fn fallback_location(&self) -> Result<LocationInfo> {
+        if let Some(location) = &*self.fallback.lock().unwrap() {
+            return Ok(location.clone());
+        }
+        let (lat, lon) = self
+            .config
+            .fallback_coordinates
+            .as_ref()
+            .error("No location was provided")?;
+        debug!("autolocation unavailable, using fallback_coordinates");
+        let location =
+            Self::get_location_query(lat, lon, self.config, &self.client, &self.stats).await?;
+        *self.fallback.lock().unwrap() = Some(location.clone());
+        Ok(location)
+    }
+
+    /// The location of the autolocated coordinates, only resolved again when they change
+    async fn autolocated_location(&self, coords: &Coordinates) -> Result<LocationInfo> {
+        let lat = coords.latitude.to_string();
+        let lon = coords.longitude.to_string();
+        let point = format!("{},{}", round_coordinate(&lat), round_coordinate(&lon));
+        if let Some(location) = &*self.autolocated.lock().unwrap() {
+            if location.point == point {
+                return Ok(location.clone());
+            }
+        }
+        let location =
+            Self::get_location_query(&lat, &lon, self.config, &self.client, &self.stats).await?;
+        *self.autolocated.lock().unwrap() = Some(location.clone());
+        Ok(location)
+    }
+
+    /// Build the location directly from `office`, `grid_x` and `grid_y`, if they are set
+    async fn gridpoint_location(
+        autolocate: bool,
+        config: &Config,
+        client: &reqwest::Client,
+        stats: &Stats,
+    ) -> Result<Option<LocationInfo>> {
+        let (office, x, y) = match (&config.office, config.grid_x, config.grid_y) {
+            (None, None, None) => return Ok(None),
+            (Some(office), Some(x), Some(y)) => (office.trim().to_uppercase(), x, y),
+            _ => return Err(Error::new("office, grid_x and grid_y must all be set")),
+        };
+        if autolocate || config.coordinates.is_some() || !config.locations.is_empty() {
+            return Err(Error::new(
+                "office and grid cannot be combined with coordinates, locations or autolocate",
+            ));
+        }
+        if config.show_alerts {
+            return Err(Error::new("show_alerts requires coordinates"));
+        }
+        let station = if config.use_observations {
+            let stations_url = config.gridpoint_stations_url(&office, x, y);
+            Some(Self::resolve_station(&stations_url, config, client, stats).await?)
         } else {
-            self.location.clone().error("No location was provided")?
+            None
         };
+        Ok(Some(LocationInfo {
+            query: config.gridpoint_forecast_url(&office, x, y),
+            name: format!("{office} {x},{y}"),
+            distance: None,
+            bearing: None,
+            point: String::new(),
+            station,
+            grid_point: Some(GridPoint { office, x, y }),
+        }))
+    }
 
-        let data: ApiForecastResponse = REQWEST_CLIENT
-            .get(location.query)
-            .header(
-                "Feature-Flags",
-                "forecast_wind_speed_qv,forecast_temperature_qv",
-            )
-            .send()
+    /// Resolve the location of `station`, if it is set. Observations are then taken from that
+    /// station too, instead of the one closest to it.
+    async fn station_location(
+        autolocate: bool,
+        config: &Config,
+        client: &reqwest::Client,
+        stats: &Stats,
+    ) -> Result<Option<LocationInfo>> {
+        let Some(station) = &config.station else {
+            return Ok(None);
+        };
+        if autolocate
+            || config.coordinates.is_some()
+            || !config.locations.is_empty()
+            || config.office.is_some()
+        {
+            return Err(Error::new(
+                "station cannot be combined with coordinates, locations, office or autolocate",
+            ));
+        }
+        let (lat, lon) = Self::station_coordinates(station, config, client, stats).await?;
+        let (lat, lon) = (lat.to_string(), lon.to_string());
+        let mut location = Self::get_location_query(&lat, &lon, config, client, stats).await?;
+        if config.use_observations {
+            location.station = Some(station.clone());
+        }
+        Ok(Some(location))
+    }
+
+    /// Latitude and longitude of an observation station
+    async fn station_coordinates(
+        station: &str,
+        config: &Config,
+        client: &reqwest::Client,
+        stats: &Stats,
+    ) -> Result<(f64, f64)> {
+        let station_url = config.station_url(station);
+        dry_run(config, || client.get(&station_url))?;
+        let response: ApiStationGeometry = send_request(config, stats, || client.get(&station_url))
             .await
-            .error("weather request failed")?
+            .map_err(|err| match err.status() {
+                Some(reqwest::StatusCode::NOT_FOUND) => {
+                    Error::new(format!("Unknown station {station}"))
+                }
+                _ => request_error("Station request")(err),
+            })?
             .json()
             .await
-            .error("parsing weather data failed")?;
+            .error("Failed to parse station")?;
+        let [lon, lat] = response.geometry.coordinates;
+        debug!("station {station} is at {lat},{lon}");
+        Ok((lat, lon))
+    }
 
-        let data = data.properties.periods;
-        let current = data.first().error("No current weather")?;
+    async fn get_location_query(
+        lat: &str,
+        lon: &str,
+        config: &Config,
+        client: &reqwest::Client,
+        stats: &Stats,
+    ) -> Result<LocationInfo> {
+        let cache_path = location_cache_path(lat, lon, config);
+        if let Some(path) = &cache_path {
+            if let Some(location) = load_cached_location(path, config.location_cache_ttl.0).await {
+                debug!("using cached location from {}", path.display());
+                stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(location);
+            }
+            stats.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
 
-        let current_weather = current.to_moment();
+        let location = Self::resolve_location(lat, lon, config, client, stats).await?;
 
-        if !need_forecast {
-            return Ok(WeatherResult {
-                location: location.name,
-                current_weather,
-                forecast: None,
-            });
+        if let Some(path) = &cache_path {
+            if let Err(err) = store_cached_location(path, &location).await {
+                debug!("failed to cache location: {err}");
+            }
         }
+        Ok(location)
+    }
 
-        let data_agg: Vec<ForecastAggregate> = data
-            .iter()
-            .take(self.config.forecast_hours)
-            .map(|f| f.to_aggregate())
-            .collect();
+    async fn resolve_location(
+        lat: &str,
+        lon: &str,
+        config: &Config,
+        client: &reqwest::Client,
+        stats: &Stats,
+    ) -> Result<LocationInfo> {
+        let points_url = config.points_url(lat, lon);
+        dry_run(config, || client.get(&points_url))?;
 
-        let fin = data
-            .get(self.config.forecast_hours.min(data.len() - 1))
-            .error("no weather available")?
-            .to_moment();
+        let response: ApiPoints = send_request(config, stats, || client.get(&points_url))
+            .await
+            .map_err(|err| match err.status() {
+                Some(reqwest::StatusCode::NOT_FOUND) => Error::new(format!(
+                    "NWS has no coverage for {lat},{lon} (got 404)"
+                )),
+                _ => request_error("Zone resolution request")(err),
+            })?
+            .json()
+            .await
+            .error("NWS returned malformed zone resolution data")?;
+        let properties = response.properties;
+        let query = config.rebase_url(match config.forecast_mode {
+            ForecastMode::Hourly => properties.forecast_hourly,
+            ForecastMode::Daily => properties.forecast,
+        });
+        let station = match properties.observation_stations {
+            Some(stations_url) if config.use_observations => {
+                let stations_url = config.rebase_url(stations_url);
+                Some(Self::resolve_station(&stations_url, config, client, stats).await?)
+            }
+            _ => None,
+        };
+        let grid_point = match (properties.grid_id, properties.grid_x, properties.grid_y) {
+            (Some(office), Some(x), Some(y)) => Some(GridPoint { office, x, y }),
+            _ => None,
+        };
+        let location = properties.relative_location.properties;
+        let name = format!("{}, {}", location.city, location.state);
+        let point = format!("{},{}", round_coordinate(lat), round_coordinate(lon));
+        debug!("resolved {point} to {name}, forecast at {query}");
+        Ok(LocationInfo {
+            query,
+            name,
+            distance: location.distance.value,
+            bearing: location.bearing.value,
+            point,
+            station,
+            grid_point,
+        })
+    }
 
-        let forecast = Some(combine_forecasts(&data_agg, fin));
+    /// Find the observation station closest to the location. The API lists them by distance.
+    async fn resolve_station(
+        stations_url: &str,
+        config: &Config,
+        client: &reqwest::Client,
+        stats: &Stats,
+    ) -> Result<String> {
+        dry_run(config, || client.get(stations_url))?;
+        let response: ApiStations = send_request(config, stats, || client.get(stations_url))
+            .await
+            .map_err(request_error("Observation stations request"))?
+            .json()
+            .await
+            .error("Failed to parse observation stations")?;
+        response
+            .features
+            .into_iter()
+            .next()
+            .map(|station| station.properties.station_identifier)
+            .error("No observation station found")
+    }
 
-        Ok(WeatherResult {
-            location: location.name,
-            current_weather,
-            forecast,
+    async fn get_observation(&self, station: &str) -> Result<ApiObservationProperties> {
+        let observation_url = self.config.observation_url(station);
+        dry_run(self.config, || self.client.get(&observation_url))?;
+        let response: ApiObservation =
+            send_request(self.config, &self.stats, || self.client.get(&observation_url))
+                .await
+                .map_err(request_error("Observation request"))?
+                .json()
+                .await
+                .error("Failed to parse observation")?;
+        Ok(response.properties)
+    }
+
+    /// Fetch the hourly or daily forecast at `url`
+    async fn get_forecast(&self, url: &str) -> Result<ApiForecastProperties> {
+        let feature_flags = self.config.feature_flags.join(",");
+        let request = || {
+            let request = self.client.get(url);
+            if feature_flags.is_empty() {
+                request
+            } else {
+                request.header("Feature-Flags", &feature_flags)
+            }
+        };
+        dry_run(self.config, request)?;
+        let data: ApiForecastResponse = send_request(self.config, &self.stats, request)
+            .await
+            .map_err(request_error("weather request"))?
+            .json()
+            .await
+            .error("parsing weather data failed")?;
+        Ok(data.properties)
+    }
+
+    /// Fetch the daily forecast next to the hourly one at `hourly_url`
+    async fn get_daily_forecast(&self, hourly_url: &str) -> Result<Vec<ApiForecast>> {
+        let url = hourly_url
+            .strip_suffix("/hourly")
+            .or_error(|| format!("No daily forecast known for {hourly_url}"))?;
+        Ok(self.get_forecast(url).await?.periods)
+    }
+
+    async fn get_alerts(&self, point: &str) -> Result<WeatherAlerts> {
+        let alerts_url = self.config.alerts_url(point);
+        dry_run(self.config, || self.client.get(&alerts_url))?;
+
+        let request = || self.client.get(&alerts_url);
+        let response: ApiAlerts = send_request(self.config, &self.stats, request)
+            .await
+            .map_err(request_error("Alerts request"))?
+            .json()
+            .await
+            .error("Failed to parse alerts")?;
+
+        let count = response.features.len();
+        let most_severe = response
+            .features
+            .into_iter()
+            .map(|feature| feature.properties)
+            .max_by_key(|alert| alert.severity);
+        Ok(WeatherAlerts {
+            count,
+            event: most_severe.as_ref().map(|alert| alert.event.clone()),
+            headline: most_severe.and_then(|alert| alert.headline),
         })
     }
 }
 
-/// Try to turn the short forecast into an icon.
+/// Send a request, retrying with exponential backoff on server errors and timeouts.
 ///
-/// The official API has an icon field, but it's been marked as deprecated.
-/// Unfortunately, the short forecast cannot actually be fully enumerated, so
-/// we're reduced to checking for the presence of specific strings.
-fn short_forecast_to_icon(weather: &str, is_night: bool) -> WeatherIcon {
-    let weather = weather.to_lowercase();
-    // snow, flurries, flurry, blizzard
-    if weather.contains("snow") || weather.contains("flurr") || weather.contains("blizzard") {
-        return WeatherIcon::Snow;
+/// Failures to resolve the host or to connect to it are retried separately, at a fixed delay,
+/// since the network often takes a moment to come back after resuming from suspend. Client
+/// errors are not retried, since they won't go away by themselves. The configured
+/// `User-Agent` is attached to every request, as the NWS requires one that identifies the
+/// application.
+async fn send_request(
+    config: &Config,
+    stats: &Stats,
+    request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let backoff = ExponentialBuilder::default()
+        .with_min_delay(config.retry_delay.0)
+        .with_max_times(config.retries);
+    let connect_backoff = ConstantBuilder::default()
+        .with_delay(config.connect_retry_delay.0)
+        .with_max_times(config.connect_retries);
+    let send = || async {
+        if let Some(per_minute) = config.max_requests_per_minute {
+            let wait = RATE_LIMITER.lock().unwrap().acquire(per_minute, Instant::now());
+            if !wait.is_zero() {
+                debug!("rate limited, waiting {wait:?}");
+                tokio::time::sleep(wait).await;
+            }
+        }
+        stats.requests.fetch_add(1, Ordering::Relaxed);
+        let response = request()
+            .header(reqwest::header::USER_AGENT, &config.user_agent)
+            .timeout(config.timeout.0)
+            .send()
+            .await?;
+        debug!("GET {} returned {}", response.url(), response.status());
+        response.error_for_status()
+    };
+    let send_with_retries = || {
+        send.retry(&backoff)
+            .when(|err| err.is_timeout() || err.status().is_some_and(|s| s.is_server_error()))
+    };
+    let result = send_with_retries
+        .retry(&connect_backoff)
+        .when(reqwest::Error::is_connect)
+        .await;
+    if result.is_err() {
+        stats.failures.fetch_add(1, Ordering::Relaxed);
     }
-    // thunderstorms
-    if weather.contains("thunder") {
-        return WeatherIcon::Thunder { is_night };
+    result
+}
+
+/// A token bucket, refilled at the allowed rate and holding at most a minute's worth of requests
+struct RateLimiter {
+    /// Requests that can be made right away. Negative when requests are waiting for their turn.
+    tokens: f64,
+    updated: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Take a token, returning how long to wait before making the request
+    fn acquire(&mut self, per_minute: NonZeroU32, now: Instant) -> Duration {
+        let capacity = per_minute.get() as f64;
+        let rate = capacity / 60.0;
+        let tokens = match self.updated {
+            Some(updated) => {
+                (self.tokens + now.duration_since(updated).as_secs_f64() * rate).min(capacity)
+            }
+            None => capacity,
+        };
+        self.tokens = tokens - 1.0;
+        self.updated = Some(now);
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
     }
-    // fog or mist
-    if weather.contains("fog") || weather.contains("mist") {
-        return WeatherIcon::Fog { is_night };
+}
+
+/// With `dry_run`, log the request that would be sent, with its headers, and fail instead of
+/// sending it
+fn dry_run(config: &Config, request: impl FnOnce() -> reqwest::RequestBuilder) -> Result<()> {
+    if !config.dry_run {
+        return Ok(());
     }
-    // rain, rainy, shower, drizzle (drizzle might not be present)
-    if weather.contains("rain") || weather.contains("shower") || weather.contains("drizzle") {
-        return WeatherIcon::Rain { is_night };
+    let request = request()
+        .header(reqwest::header::USER_AGENT, &config.user_agent)
+        .build()
+        .error("Invalid request")?;
+    info!("dry run: GET {}", request.url());
+    for (name, value) in request.headers() {
+        info!("dry run: {name}: {}", value.to_str().unwrap_or("<binary>"));
     }
-    // cloudy, clouds, partly cloudy, overcast, etc.
-    if weather.contains("cloud") || weather.contains("overcast") {
-        return WeatherIcon::Clouds { is_night };
+    Err(Error::new(format!(
+        "Dry run, not requesting {}",
+        request.url()
+    )))
+}
+
+/// Turn a failed request into an error, telling a timeout apart from other failures such as a
+/// refused connection.
+fn request_error(what: &'static str) -> impl FnOnce(reqwest::Error) -> Error {
+    move |err| Error {
+        message: Some(if err.is_timeout() {
+            format!("{what} timed out").into()
+        } else {
+            format!("{what} failed").into()
+        }),
+        cause: Some(Arc::new(err)),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedLocation {
+    /// Unix timestamp of when the location was resolved
+    resolved_at: u64,
+    location: LocationInfo,
+}
+
+/// Path of the cache file for the given coordinates, rounded so that insignificant differences in
+/// the coordinates still hit the same entry.
+fn location_cache_path(lat: &str, lon: &str, config: &Config) -> Option<PathBuf> {
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lon: f64 = lon.trim().parse().ok()?;
+    let mode = match config.forecast_mode {
+        ForecastMode::Hourly => "hourly",
+        ForecastMode::Daily => "daily",
+    };
+    let observations = if config.use_observations { "_obs" } else { "" };
+    // The cached forecast URL points to the API it was resolved with
+    let api = match &config.api_url {
+        Some(api_url) => format!("_{:016x}", stable_hash(api_url)),
+        None => String::new(),
+    };
+    let mut path = dirs::cache_dir()?;
+    path.push("i3status-rust");
+    path.push("nws");
+    path.push(format!(
+        "points_{lat:.4}_{lon:.4}_{mode}{observations}{api}.json"
+    ));
+    Some(path)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Returns `None` if the cache entry is missing, unreadable, or older than `ttl`.
+async fn load_cached_location(path: &Path, ttl: Duration) -> Option<LocationInfo> {
+    let contents = tokio::fs::read(path).await.ok()?;
+    let cached: CachedLocation = serde_json::from_slice(&contents).ok()?;
+    let age = unix_now().checked_sub(cached.resolved_at)?;
+    let ttl = jittered_ttl(ttl, &path.file_name()?.to_string_lossy());
+    (age < ttl.as_secs()).then_some(cached.location)
+}
+
+/// Shift `ttl` by up to a tenth, so that locations cached at the same time are not all resolved
+/// again at once. The shift only depends on `key`, so that an entry doesn't flip between
+/// expired and valid from one check to the next.
+fn jittered_ttl(ttl: Duration, key: &str) -> Duration {
+    Jitter::with_seed(ttl / 10, stable_hash(key)).apply(ttl)
+}
+
+/// FNV-1a, for hashes that have to stay the same from one run to the next
+fn stable_hash(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+async fn store_cached_location(path: &Path, location: &LocationInfo) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .error("Failed to create cache directory")?;
+    }
+    let cached = CachedLocation {
+        resolved_at: unix_now(),
+        location: location.clone(),
+    };
+    let contents = serde_json::to_vec(&cached).error("Failed to serialize location")?;
+    tokio::fs::write(path, contents)
+        .await
+        .error("Failed to write location cache")
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiPoints {
+    properties: ApiPointsProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiPointsProperties {
+    forecast: String,
+    forecast_hourly: String,
+    relative_location: ApiRelativeLocation,
+    #[serde(default)]
+    observation_stations: Option<String>,
+    #[serde(default)]
+    grid_id: Option<String>,
+    #[serde(default)]
+    grid_x: Option<u32>,
+    #[serde(default)]
+    grid_y: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiStations {
+    features: Vec<ApiStation>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiStation {
+    properties: ApiStationProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiStationProperties {
+    station_identifier: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiStationGeometry {
+    geometry: ApiPoint,
+}
+
+/// A GeoJSON point
+#[derive(Deserialize, Debug)]
+struct ApiPoint {
+    /// Longitude and latitude, in that order
+    coordinates: [f64; 2],
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiObservation {
+    properties: ApiObservationProperties,
+}
+
+/// Latest observation of a station. Values are always in SI units, and any of them may be
+/// missing.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiObservationProperties {
+    #[serde(default)]
+    text_description: String,
+    #[serde(default)]
+    temperature: ApiOptionalValue,
+    #[serde(default)]
+    dewpoint: ApiOptionalValue,
+    #[serde(default)]
+    relative_humidity: ApiOptionalValue,
+    #[serde(default)]
+    wind_direction: ApiOptionalValue,
+    #[serde(default)]
+    wind_speed: ApiOptionalValue,
+    #[serde(default)]
+    wind_gust: ApiOptionalValue,
+    #[serde(default)]
+    barometric_pressure: ApiOptionalValue,
+}
+
+impl ApiObservationProperties {
+    /// Replace the values of `forecast` by the observed ones, where available
+    fn to_moment(&self, config: &Config, forecast: WeatherMoment, is_night: bool) -> WeatherMoment {
+        let units = config.units;
+        let temp_unit = config.temp_unit();
+        let temp_celsius = self.temperature.temperature().map(Temperature::celsius);
+        let humidity = self
+            .relative_humidity
+            .value
+            .filter(|humidity| humidity.is_finite())
+            .map_or(forecast.humidity, clamp_humidity);
+        let wind = self.wind_speed.speed();
+        let wind_gust = self.wind_gust.speed();
+        let wind_kmh = wind.map_or(forecast.wind_kmh, Speed::kmh);
+        let wind_local = wind.map_or(forecast.wind, |wind| wind.local(units));
+
+        let (icon, weather, weather_verbose) = if self.text_description.is_empty() {
+            (forecast.icon, forecast.weather, forecast.weather_verbose)
+        } else {
+            let icon = short_forecast_to_icon(&self.text_description, is_night);
+            (icon, icon.to_word().into(), self.text_description.clone())
+        };
+
+        WeatherMoment {
+            icon,
+            weather,
+            weather_verbose,
+            weather_detailed: forecast.weather_detailed,
+            temp: temp_celsius.map_or(forecast.temp, |temp| temp_unit.convert_celsius(temp)),
+            apparent: temp_celsius.map_or(forecast.apparent, |temp| {
+                let apparent = config.apparent_celsius(temp, humidity, wind_kmh);
+                temp_unit.convert_celsius(apparent)
+            }),
+            humidity,
+            dewpoint: self
+                .dewpoint
+                .temperature()
+                .map(|dewpoint| dewpoint.local(temp_unit))
+                .or(forecast.dewpoint),
+            pressure: self
+                .barometric_pressure
+                .value
+                .map(|pascal| config.pressure_unit().convert_hpa(pascal / 100.0))
+                .or(forecast.pressure),
+            visibility: forecast.visibility,
+            wind: wind_local,
+            wind_kmh,
+            wind_mph: wind.map_or(forecast.wind_mph, Speed::mph),
+            wind_direction: config.calm_direction(
+                wind_local,
+                self.wind_direction.value.or(forecast.wind_direction),
+            ),
+            wind_gust: wind_gust
+                .map(|gust| gust.local(units))
+                .or(forecast.wind_gust),
+            wind_gust_kmh: wind_gust.map(Speed::kmh).or(forecast.wind_gust_kmh),
+            precip_prob: forecast.precip_prob,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiRelativeLocation {
+    properties: ApiRelativeLocationProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiRelativeLocationProperties {
+    city: String,
+    state: String,
+    #[serde(default)]
+    distance: ApiOptionalValue,
+    #[serde(default)]
+    bearing: ApiOptionalValue,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiAlerts {
+    features: Vec<ApiAlert>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiAlert {
+    properties: ApiAlertProperties,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiAlertProperties {
+    event: String,
+    severity: ApiAlertSeverity,
+    headline: Option<String>,
+}
+
+/// Alert severities, ordered from least to most severe
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ApiAlertSeverity {
+    Minor = 1,
+    Moderate = 2,
+    Severe = 3,
+    Extreme = 4,
+    #[serde(other)]
+    Unknown = 0,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiForecastResponse {
+    properties: ApiForecastProperties,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiForecastProperties {
+    periods: Vec<ApiForecast>,
+    #[serde(default)]
+    update_time: Option<String>,
+}
+
+impl ApiForecastProperties {
+    /// When the forecast was last updated. `None` if missing or unparsable.
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        parse_time(self.update_time.as_deref()?)
+    }
+}
+
+/// Parse a timestamp from the API, logging failures
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(time) {
+        Ok(time) => Some(time.with_timezone(&Utc)),
+        Err(err) => {
+            debug!("failed to parse time {time:?}: {err}");
+            None
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "ApiValueShape")]
+struct ApiValue {
+    value: f64,
+    unit_code: String,
+}
+
+impl TryFrom<ApiValueShape> for ApiValue {
+    type Error = &'static str;
+
+    fn try_from(shape: ApiValueShape) -> Result<Self, Self::Error> {
+        let ApiOptionalValue { value, unit_code } = shape.into();
+        Ok(Self {
+            value: value.ok_or("missing value")?,
+            unit_code,
+        })
+    }
+}
+
+impl ApiValue {
+    fn speed(&self) -> Speed {
+        Speed::new(self.value, &self.unit_code)
+    }
+
+    fn temperature(&self) -> Temperature {
+        Temperature::new(self.value, &self.unit_code)
+    }
+}
+
+/// Like [`ApiValue`], but the value may be `null`
+#[derive(Deserialize, Debug, Default)]
+#[serde(from = "ApiValueShape")]
+struct ApiOptionalValue {
+    value: Option<f64>,
+    unit_code: String,
+}
+
+/// The shapes a value can come in. Only `Value` is used by the forecast at the time of
+/// writing, but the others are accepted in case the API changes.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ApiValueShape {
+    /// A range, e.g. `{ "unitCode": "wmoUnit:km_h-1", "minValue": 8, "maxValue": 16 }`
+    #[serde(rename_all = "camelCase")]
+    Range {
+        #[serde(default)]
+        unit_code: String,
+        min_value: f64,
+        max_value: f64,
+    },
+    /// A time series, as in the grid data:
+    /// `{ "uom": "wmoUnit:degC", "values": [{ "validTime": "...", "value": 25 }] }`
+    Series {
+        #[serde(default)]
+        uom: String,
+        values: Vec<ApiSeriesValue>,
+    },
+    /// `{ "unitCode": "wmoUnit:degC", "value": 25 }`
+    #[serde(rename_all = "camelCase")]
+    Value {
+        #[serde(default)]
+        unit_code: String,
+        value: Option<f64>,
+    },
+    /// A bare number, without a unit
+    Scalar(f64),
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiSeriesValue {
+    value: Option<f64>,
+}
+
+impl From<ApiValueShape> for ApiOptionalValue {
+    fn from(shape: ApiValueShape) -> Self {
+        let (value, unit_code) = match shape {
+            ApiValueShape::Value { unit_code, value } => (value, unit_code),
+            ApiValueShape::Range {
+                unit_code,
+                min_value,
+                max_value,
+            } => {
+                debug!("parsed a range value");
+                (Some((min_value + max_value) / 2.0), unit_code)
+            }
+            ApiValueShape::Series { uom, values } => {
+                debug!("parsed a time series value");
+                (values.first().and_then(|value| value.value), uom)
+            }
+            ApiValueShape::Scalar(value) => {
+                debug!("parsed a value without a unit");
+                (Some(value), String::new())
+            }
+        };
+        Self { value, unit_code }
+    }
+}
+
+impl ApiOptionalValue {
+    fn speed(&self) -> Option<Speed> {
+        self.value.map(|value| Speed::new(value, &self.unit_code))
+    }
+
+    fn temperature(&self) -> Option<Temperature> {
+        self.value
+            .map(|value| Temperature::new(value, &self.unit_code))
+    }
+}
+
+/// A speed reported by the API, stored in m/s whatever unit the API used
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Speed(f64);
+
+impl Speed {
+    fn new(value: f64, unit_code: &str) -> Self {
+        if unit_code.ends_with("km_h-1") {
+            Self(value / 3.6)
+        } else if unit_code.ends_with("m_s-1") {
+            Self(value)
+        } else if unit_code.ends_with(":kt") {
+            Self(value * KNOT_TO_MS)
+        } else {
+            Self(value * MPH_TO_KPH / 3.6)
+        }
+    }
+
+    fn ms(self) -> f64 {
+        self.0
+    }
+
+    fn kmh(self) -> f64 {
+        self.ms() * 3.6
+    }
+
+    fn mph(self) -> f64 {
+        self.kmh() / MPH_TO_KPH
+    }
+
+    /// The speed in km/h for metric units, and in mph for imperial units
+    fn local(self, units: UnitSystem) -> f64 {
+        units.speed_from_kmh(self.kmh())
+    }
+}
+
+/// A temperature reported by the API, stored in °C whatever unit the API used
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Temperature(f64);
+
+impl Temperature {
+    fn new(value: f64, unit_code: &str) -> Self {
+        if unit_code.ends_with("degC") {
+            Self(value)
+        } else if unit_code.ends_with(":K") {
+            Self(value - 273.15)
+        } else {
+            Self((value - 32.0) * 5.0 / 9.0)
+        }
+    }
+
+    fn celsius(self) -> f64 {
+        self.0
+    }
+
+    /// The temperature in the given unit
+    fn local(self, unit: TempUnit) -> f64 {
+        unit.convert_celsius(self.celsius())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApiForecast {
+    #[serde(default)]
+    start_time: Option<String>,
+    #[serde(default)]
+    end_time: Option<String>,
+    is_daytime: bool,
+    temperature: ApiValue,
+    /// Sometimes `null`, or slightly out of range
+    #[serde(default)]
+    relative_humidity: ApiOptionalValue,
+    wind_speed: ApiValue,
+    wind_direction: String,
+    short_forecast: String,
+    #[serde(default)]
+    detailed_forecast: String,
+    #[serde(default)]
+    probability_of_precipitation: ApiOptionalValue,
+    #[serde(default)]
+    wind_gust: ApiOptionalValue,
+    #[serde(default)]
+    dewpoint: ApiOptionalValue,
+    /// Deprecated, but still sent
+    #[serde(default)]
+    icon: Option<String>,
+}
+
+impl ApiForecast {
+    fn wind_direction(&self) -> f64 {
+        let dir = match self.wind_direction.as_str() {
+            "NNE" => 1,
+            "NE" => 2,
+            "ENE" => 3,
+            "E" => 4,
+            "ESE" => 5,
+            "SE" => 6,
+            "SSE" => 7,
+            "S" => 8,
+            "SSW" => 9,
+            "SW" => 10,
+            "WSW" => 11,
+            "W" => 12,
+            "WNW" => 13,
+            "NW" => 14,
+            "NNW" => 15,
+            _ => 0,
+        };
+        (dir as f64) * (360.0 / 16.0)
+    }
+
+    /// The temperature in the configured unit, whatever unit the API used
+    fn temp(&self, unit: TempUnit) -> f64 {
+        self.temperature.temperature().local(unit)
+    }
+
+    fn apparent_temp(&self, config: &Config) -> f64 {
+        let temp = self.temperature.temperature().celsius();
+        let wind_kmh = self.wind_speed.speed().kmh();
+        let apparent = config.apparent_celsius(temp, self.humidity(), wind_kmh);
+        config.temp_unit().convert_celsius(apparent)
+    }
+
+    /// The relative humidity within 0 to 100%, or a neutral one if missing
+    fn humidity(&self) -> f64 {
+        self.relative_humidity
+            .value
+            .filter(|humidity| humidity.is_finite())
+            .map_or(NEUTRAL_HUMIDITY, clamp_humidity)
+    }
+
+    fn dewpoint(&self, unit: TempUnit) -> Option<f64> {
+        self.dewpoint
+            .temperature()
+            .map(|dewpoint| dewpoint.local(unit))
+    }
+
+    fn precip_prob(&self) -> f64 {
+        self.probability_of_precipitation.value.unwrap_or_default()
+    }
+
+    fn to_moment(&self, config: &Config) -> WeatherMoment {
+        let icon = self
+            .icon
+            .as_deref()
+            .filter(|_| config.use_api_icon)
+            .and_then(api_icon_to_icon)
+            .unwrap_or_else(|| short_forecast_to_icon(&self.short_forecast, !self.is_daytime));
+        let weather = icon.to_word().to_string();
+        let wind = self.wind_speed.speed();
+        let wind_gust = self.wind_gust.speed();
+        WeatherMoment {
+            icon,
+            weather,
+            weather_verbose: self.short_forecast.clone(),
+            weather_detailed: (!self.detailed_forecast.is_empty())
+                .then(|| self.detailed_forecast.clone()),
+            temp: self.temp(config.temp_unit()),
+            apparent: self.apparent_temp(config),
+            humidity: self.humidity(),
+            dewpoint: self.dewpoint(config.temp_unit()),
+            pressure: None,
+            visibility: None,
+            wind: wind.local(config.units),
+            wind_kmh: wind.kmh(),
+            wind_mph: wind.mph(),
+            wind_direction: config
+                .calm_direction(wind.local(config.units), Some(self.wind_direction())),
+            wind_gust: wind_gust.map(|gust| gust.local(config.units)),
+            wind_gust_kmh: wind_gust.map(Speed::kmh),
+            precip_prob: Some(self.precip_prob()),
+        }
+    }
+
+    fn to_aggregate(&self, config: &Config) -> ForecastAggregate {
+        let wind = self.wind_speed.speed();
+        let wind_gust = self.wind_gust.speed();
+        ForecastAggregate {
+            temp: self.temp(config.temp_unit()),
+            apparent: self.apparent_temp(config),
+            humidity: self.humidity(),
+            dewpoint: self.dewpoint(config.temp_unit()),
+            pressure: None,
+            visibility: None,
+            wind: wind.local(config.units),
+            wind_kmh: wind.kmh(),
+            wind_mph: wind.mph(),
+            wind_direction: config
+                .calm_direction(wind.local(config.units), Some(self.wind_direction())),
+            wind_gust: wind_gust.map(|gust| gust.local(config.units)),
+            wind_gust_kmh: wind_gust.map(Speed::kmh),
+            precip_prob: Some(self.precip_prob()),
+            start: self.start_time.as_deref().and_then(parse_time),
+        }
+    }
+}
+
+/// Pair the daytime highs with the nighttime lows of the daily forecast. The first period is
+/// "Tonight" in the evening, in which case there is no high for today.
+fn daily_summary(periods: &[ApiForecast], unit: TempUnit) -> DailySummary {
+    let mut days: Vec<(Option<f64>, Option<f64>)> = Vec::new();
+    for period in periods.iter().take(4) {
+        let temp = period.temp(unit);
+        if period.is_daytime {
+            days.push((Some(temp), None));
+        } else {
+            match days.last_mut() {
+                Some((_, low @ None)) => *low = Some(temp),
+                _ => days.push((None, Some(temp))),
+            }
+        }
+    }
+    let day = |i: usize| days.get(i).copied().unwrap_or_default();
+    DailySummary {
+        today_high: day(0).0,
+        today_low: day(0).1,
+        tomorrow_high: day(1).0,
+        tomorrow_low: day(1).1,
+    }
+}
+
+/// The API rejects coordinates with more than four decimal places
+fn round_coordinate(coordinate: &str) -> String {
+    let coordinate = coordinate.trim();
+    match coordinate.parse::<f64>() {
+        Ok(value) => {
+            let rounded = format!("{value:.4}");
+            rounded
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        }
+        Err(_) => coordinate.to_string(),
+    }
+}
+
+/// Rounding sometimes puts the humidity slightly out of range
+fn clamp_humidity(humidity: f64) -> f64 {
+    humidity.clamp(0.0, 100.0)
+}
+
+
+#[async_trait]
+impl WeatherProvider for Service<'_> {
+    async fn get_weather(
+        &self,
+        autolocated: Option<&Coordinates>,
+        need_forecast: bool,
+    ) -> Result<WeatherResult> {
+        let location = if let Some(coords) = autolocated {
+            self.autolocated_location(coords).await?
+        } else if self.locations.is_empty() {
+            self.fallback_location().await?
+        } else {
+            let current = self.current.load(Ordering::Relaxed);
+            self.locations[current % self.locations.len()].clone()
+        };
+
+        let data = self.get_forecast(&location.query).await?;
+        debug!("{}", self.stats);
+        let updated = data.updated();
+        let data = data.periods;
+        debug!("{}: received {} forecast periods", location.name, data.len());
+        let periods = self.config.forecast_periods();
+        let current_index = self.config.current_period.index(&data, Utc::now());
+        debug!("using forecast period {current_index} as the current weather");
+        // Periods before the current one have already ended
+        let upcoming = &data[current_index.min(data.len())..];
+        let current = upcoming.first().or_error(|| {
+            format!("No forecast periods received, expected {}", periods.max(1))
+        })?;
+
+        let mut current_weather = current.to_moment(self.config);
+        if let Some(station) = &location.station {
+            match self.get_observation(station).await {
+                Ok(observation) => {
+                    current_weather =
+                        observation.to_moment(self.config, current_weather, !current.is_daytime);
+                }
+                Err(err) => {
+                    debug!("failed to get the latest observation: {err}");
+                }
+            }
+        }
+
+        let forecast = if need_forecast {
+            let offset = self.config.forecast_offset;
+            if offset >= upcoming.len() {
+                return Err(Error::new(format!(
+                    "forecast_offset is {offset}, but only {} periods are available",
+                    upcoming.len()
+                )));
+            }
+            if upcoming.len() < offset + periods {
+                debug!(
+                    "forecast window truncated: expected {periods} periods, received {}",
+                    upcoming.len() - offset
+                );
+            }
+            let data_agg: Vec<ForecastAggregate> = upcoming
+                .iter()
+                .skip(offset)
+                .take(periods)
+                .map(|f| f.to_aggregate(self.config))
+                .collect();
+
+            // `offset` is within `upcoming`, so there always is a final period
+            let fin = self
+                .config
+                .final_period
+                .index(offset, data_agg.len(), upcoming.len())
+                .map(|i| upcoming[i].to_moment(self.config));
+
+            let mut forecast = combine_forecasts(
+                &data_agg,
+                fin,
+                self.config.aggregate,
+                self.config.aggregate_weighting,
+                self.config.couple_apparent,
+                self.config.extrema_mode,
+            );
+            // Calm periods have no direction, and don't count towards the average one. The
+            // average wind can still be calm when the periods aren't.
+            let avg = &mut forecast.avg;
+            avg.wind_direction = self.config.calm_direction(avg.wind, avg.wind_direction);
+            Some(forecast)
+        } else {
+            None
+        };
+
+        let alerts = if self.config.show_alerts {
+            match self.get_alerts(&location.point).await {
+                Ok(alerts) => Some(alerts),
+                Err(err) => {
+                    debug!("failed to get the alerts: {err}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let daily = if !self.config.show_daily_summary {
+            None
+        } else if self.config.forecast_mode == ForecastMode::Daily {
+            Some(daily_summary(&data, self.config.temp_unit()))
+        } else {
+            match self.get_daily_forecast(&location.query).await {
+                Ok(daily) => Some(daily_summary(&daily, self.config.temp_unit())),
+                Err(err) => {
+                    debug!("failed to get the daily forecast: {err}");
+                    None
+                }
+            }
+        };
+
+        Ok(WeatherResult {
+            location: self.config.location_name(&location.name),
+            location_distance: location
+                .distance
+                .map(|meters| self.config.units.distance_from_meters(meters)),
+            location_bearing: location.bearing,
+            current_weather,
+            forecast,
+            alerts,
+            updated,
+            periods: upcoming
+                .iter()
+                .skip(1)
+                .take(periods)
+                .map(|period| ForecastPeriod {
+                    start: period.start_time.as_deref().and_then(parse_time),
+                    weather: period.to_moment(self.config),
+                })
+                .collect(),
+            coordinates: location
+                .point
+                .split_once(',')
+                .and_then(|(lat, lon)| parse_coordinates(lat, lon)),
+            temp_unit: self.config.temp_unit(),
+            pressure_unit: self.config.pressure_unit(),
+            units: self.config.units,
+            provider: "nws",
+            debug_stats: Some(self.stats.to_string()),
+            daily,
+            grid_point: location.grid_point,
+        })
+    }
+
+    fn retries_requests(&self) -> bool {
+        true
+    }
+
+    fn has_several_locations(&self) -> bool {
+        self.locations.len() > 1
+    }
+
+    fn cycle_location(&self) -> bool {
+        if !self.has_several_locations() {
+            return false;
+        }
+        let next = (self.current.load(Ordering::Relaxed) + 1) % self.locations.len();
+        self.current.store(next, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Try to turn the short forecast into an icon.
+///
+/// The official API has an icon field, but it's been marked as deprecated.
+/// Unfortunately, the short forecast cannot actually be fully enumerated, so
+/// we're reduced to checking for the presence of specific strings.
+fn short_forecast_to_icon(weather: &str, is_night: bool) -> WeatherIcon {
+    let weather = weather.to_lowercase();
+    // sleet, ice pellets, freezing rain/drizzle/spray. These must come before the snow and
+    // rain checks, since e.g. "Freezing Rain" contains "rain" and "Snow And Sleet" contains
+    // "snow". "Freezing Fog" is left to the fog check.
+    if weather.contains("sleet")
+        || weather.contains("ice pellets")
+        || (weather.contains("freezing") && !weather.contains("fog"))
+    {
+        return WeatherIcon::Sleet { is_night };
+    }
+    // snow, flurries, flurry, blizzard
+    if weather.contains("snow") || weather.contains("flurr") || weather.contains("blizzard") {
+        return WeatherIcon::Snow { is_night };
+    }
+    // thunderstorms
+    if weather.contains("thunder") {
+        return WeatherIcon::Thunder { is_night };
+    }
+    // hail, small hail
+    if weather.contains("hail") {
+        return WeatherIcon::Sleet { is_night };
+    }
+    // fog or mist
+    if weather.contains("fog") || weather.contains("mist") {
+        return WeatherIcon::Fog { is_night };
+    }
+    // rain, rainy, shower, drizzle (drizzle might not be present)
+    if weather.contains("rain") || weather.contains("shower") || weather.contains("drizzle") {
+        return WeatherIcon::Rain { is_night };
+    }
+    // partly cloudy, mostly cloudy, few clouds, scattered clouds, partly sunny
+    let partial = ["partly", "mostly", "few", "scattered"]
+        .iter()
+        .any(|keyword| weather.contains(keyword));
+    if weather.contains("cloud") || weather.contains("overcast") {
+        if partial && !weather.contains("overcast") {
+            return WeatherIcon::PartlyCloudy { is_night };
+        }
+        return WeatherIcon::Clouds { is_night };
+    }
+    if weather.contains("partly sunny") {
+        return WeatherIcon::PartlyCloudy { is_night };
     }
     // clear (night), sunny (day). "Mostly sunny" / "Mostly clear" fit here too
     if weather.contains("clear") || weather.contains("sunny") {
@@ -378,3 +1787,1331 @@ fn short_forecast_to_icon(weather: &str, is_night: bool) -> WeatherIcon {
     }
     WeatherIcon::Default
 }
+
+/// Parse an icon URL of the API, e.g. `https://api.weather.gov/icons/land/day/tsra,40?size=small`.
+/// Periods with changing weather have two conditions, of which the first one is used.
+fn api_icon_to_icon(url: &str) -> Option<WeatherIcon> {
+    let path = url.split(['?', '#']).next()?;
+    let mut segments = path.split('/').skip_while(|s| !matches!(*s, "land" | "marine"));
+    segments.next()?;
+    let is_night = match segments.next()? {
+        "day" => false,
+        "night" => true,
+        _ => return None,
+    };
+    let condition = segments.next()?.split(',').next()?;
+    let icon = match condition.strip_prefix("wind_").unwrap_or(condition) {
+        "skc" | "hot" | "cold" => WeatherIcon::Clear { is_night },
+        "few" | "sct" => WeatherIcon::PartlyCloudy { is_night },
+        "bkn" | "ovc" => WeatherIcon::Clouds { is_night },
+        "snow" | "blizzard" => WeatherIcon::Snow { is_night },
+        "rain_snow" | "rain_sleet" | "snow_sleet" | "fzra" | "rain_fzra" | "snow_fzra"
+        | "sleet" => WeatherIcon::Sleet { is_night },
+        "rain" | "rain_showers" | "rain_showers_hi" => WeatherIcon::Rain { is_night },
+        "tsra" | "tsra_sct" | "tsra_hi" | "tornado" | "hurricane" | "tropical_storm" => {
+            WeatherIcon::Thunder { is_night }
+        }
+        "fog" | "haze" | "smoke" | "dust" => WeatherIcon::Fog { is_night },
+        _ => return None,
+    };
+    Some(icon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write as _};
+    use std::net::TcpListener;
+
+    /// Serve `responses` as `(status, body)` pairs, one per connection, from a local port.
+    ///
+    /// Returns the base URL of the server and a counter of the requests it has received.
+    fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 4096];
+                let _ = stream.read(&mut buf).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    fn test_config() -> Config {
+        Config {
+            retry_delay: Seconds(Duration::from_millis(10)),
+            connect_retry_delay: Seconds(Duration::from_millis(10)),
+            ..Default::default()
+        }
+    }
+
+    const FORECAST_SI: &str = r#"{
+        "isDaytime": true,
+        "temperature": { "unitCode": "wmoUnit:degC", "value": 25.0 },
+        "relativeHumidity": { "unitCode": "wmoUnit:percent", "value": 50 },
+        "dewpoint": { "unitCode": "wmoUnit:degC", "value": 13.9 },
+        "windSpeed": { "unitCode": "wmoUnit:km_h-1", "value": 14.8 },
+        "windDirection": "SW",
+        "shortForecast": "Sunny"
+    }"#;
+
+    const FORECAST_US: &str = r#"{
+        "isDaytime": true,
+        "temperature": { "unitCode": "wmoUnit:degF", "value": 77 },
+        "relativeHumidity": { "unitCode": "wmoUnit:percent", "value": 50 },
+        "dewpoint": { "unitCode": "wmoUnit:degF", "value": 57.02 },
+        "windSpeed": { "unitCode": "wmoUnit:mi_h-1", "value": 9.2 },
+        "windDirection": "SW",
+        "shortForecast": "Sunny"
+    }"#;
+
+    /// Trimmed API responses for the example point of the API documentation
+    const POINTS: &str = include_str!("testdata/nws_points.json");
+    const FORECAST_HOURLY: &str = include_str!("testdata/nws_forecast_hourly.json");
+    const FORECAST_DAILY: &str = include_str!("testdata/nws_forecast_daily.json");
+
+    #[test]
+    fn test_points_fixture() {
+        let properties = serde_json::from_str::<ApiPoints>(POINTS).unwrap().properties;
+        assert_eq!(
+            properties.forecast_hourly,
+            "https://api.weather.gov/gridpoints/TOP/32,81/forecast/hourly"
+        );
+        assert_eq!(properties.grid_id.as_deref(), Some("TOP"));
+        assert_eq!((properties.grid_x, properties.grid_y), (Some(32), Some(81)));
+        let location = properties.relative_location.properties;
+        assert_eq!(location.city, "Linn");
+        assert_eq!(location.state, "KS");
+        assert_eq!(location.bearing.value, Some(358.0));
+    }
+
+    #[test]
+    fn test_forecast_fixture() {
+        let config = test_config();
+        let periods = serde_json::from_str::<ApiForecastResponse>(FORECAST_HOURLY)
+            .unwrap()
+            .properties
+            .periods;
+        assert_eq!(periods.len(), 4);
+
+        let moment = periods[0].to_moment(&config);
+        assert_eq!(moment.icon.to_icon_str(), "weather_sun");
+        assert_eq!(moment.temp, 22.2);
+        assert_eq!(moment.dewpoint, Some(9.4));
+        assert_eq!(moment.wind_direction, Some(180.0));
+
+        let data: Vec<_> = periods[..2]
+            .iter()
+            .map(|period| period.to_aggregate(&config))
+            .collect();
+        let fin = periods[2].to_moment(&config);
+        let forecast = combine_forecasts(
+            &data,
+            Some(fin),
+            Aggregate::Mean,
+            Weighting::Uniform,
+            false,
+            Extrema::PerField,
+        );
+        assert!((forecast.avg.temp - 22.75).abs() < 1e-9);
+        assert!((forecast.avg.wind_kmh - 20.372).abs() < 1e-9);
+        assert!((forecast.avg.wind_direction.unwrap() - 180.0).abs() < 1e-9);
+        assert_eq!(forecast.min.wind_kmh, 18.52);
+        assert_eq!(forecast.max.temp, 23.3);
+        let rfc3339 = |start: Option<DateTime<Utc>>| start.map(|start| start.to_rfc3339());
+        assert_eq!(
+            rfc3339(forecast.min.start),
+            Some("2024-05-01T19:00:00+00:00".into())
+        );
+        assert_eq!(
+            rfc3339(forecast.max.start),
+            Some("2024-05-01T20:00:00+00:00".into())
+        );
+        assert_eq!(forecast.fin.as_ref().unwrap().icon.to_icon_str(), "weather_sun");
+        assert_eq!(forecast.fin.as_ref().unwrap().weather_verbose, "Mostly Sunny");
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_fixtures() {
+        let stats = Stats::default();
+        let (url, requests) = mock_server(vec![(200, POINTS), (200, FORECAST_HOURLY)]);
+        let config = Config {
+            api_url: Some(format!("{url}/")),
+            forecast_hours: 2,
+            ..test_config()
+        };
+        let location =
+            Service::resolve_location("39.7456", "-97.0892", &config, &REQWEST_CLIENT, &stats)
+                .await
+                .unwrap();
+        assert_eq!(location.name, "Linn, KS");
+        assert_eq!(
+            location.query,
+            format!("{url}/gridpoints/TOP/32,81/forecast/hourly")
+        );
+
+        let service = test_service(&config, vec![location]);
+        let weather = service.get_weather(None, true).await.unwrap();
+        assert_eq!(weather.location, "Linn, KS");
+        assert_eq!(weather.provider, "nws");
+        let grid_point = GridPoint {
+            office: "TOP".into(),
+            x: 32,
+            y: 81,
+        };
+        assert_eq!(weather.grid_point.as_ref(), Some(&grid_point));
+        assert_eq!(weather.current_weather.temp, 22.2);
+        assert_eq!(weather.periods.len(), 2);
+        assert!((weather.forecast.unwrap().avg.temp - 22.75).abs() < 1e-9);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_location() {
+        let (url, requests) = mock_server(vec![(200, FORECAST_HOURLY)]);
+        let config = test_config();
+        let service = test_service(&config, Vec::new());
+        let err = service.get_weather(None, false).await.err().unwrap();
+        assert_eq!(err.message.as_deref(), Some("No location was provided"));
+
+        // Once resolved, the fallback location is reused
+        *service.fallback.lock().unwrap() = Some(location("Fallback", &url));
+        let weather = service.get_weather(None, false).await.unwrap();
+        assert_eq!(weather.location, "Fallback");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let config: Config =
+            toml::from_str("fallback_coordinates = [\"42.36\", \"-71.06\"]").unwrap();
+        let err = Service::new(false, &config, REQWEST_CLIENT.clone()).await.err().unwrap();
+        assert_eq!(
+            err.message.as_deref(),
+            Some("fallback_coordinates requires autolocate")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_autolocated_location() {
+        let (url, requests) = mock_server(vec![(200, FORECAST_HOURLY), (404, "{}")]);
+        let config = Config {
+            api_url: Some(format!("{url}/")),
+            ..test_config()
+        };
+        let service = test_service(&config, Vec::new());
+        *service.autolocated.lock().unwrap() = Some(LocationInfo {
+            point: "42.36,-71.06".into(),
+            ..location("Boston, MA", &url)
+        });
+        let coordinates = |latitude, longitude| Coordinates {
+            latitude,
+            longitude,
+            city: String::new(),
+        };
+
+        // Coordinates rounding to the same point only fetch the forecast
+        let here = coordinates(42.360001, -71.06);
+        let weather = service.get_weather(Some(&here), false).await.unwrap();
+        assert_eq!(weather.location, "Boston, MA");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Others are resolved again
+        let elsewhere = coordinates(18.2, -66.5);
+        let err = service.get_weather(Some(&elsewhere), false).await.err().unwrap();
+        assert_eq!(
+            err.message.as_deref(),
+            Some("NWS has no coverage for 18.2,-66.5 (got 404)")
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let (url, _) = mock_server(vec![(500, "{}"), (200, FORECAST_HOURLY), (404, "{}")]);
+        let config = Config {
+            retries: 1,
+            retry_delay: Seconds::new(0),
+            ..test_config()
+        };
+        let stats = Stats::default();
+        let url = format!("{url}/forecast");
+        assert!(send_request(&config, &stats, || REQWEST_CLIENT.get(&url)).await.is_ok());
+        assert!(send_request(&config, &stats, || REQWEST_CLIENT.get(&url)).await.is_err());
+        stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(stats.to_string(), "3 requests, 1 failed, 1/1 locations cached");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let stats = Stats::default();
+        let (url, requests) = mock_server(vec![(200, FORECAST_HOURLY)]);
+        let config = Config {
+            api_url: Some(format!("{url}/")),
+            dry_run: true,
+            ..test_config()
+        };
+        let err = Service::resolve_location("42.36", "-71.06", &config, &REQWEST_CLIENT, &stats)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message.unwrap(),
+            format!("Dry run, not requesting {url}/points/42.36,-71.06")
+        );
+
+        let service = test_service(&config, Vec::new());
+        let query = format!("{url}/gridpoints/BOX/71,76/forecast/hourly");
+        *service.fallback.lock().unwrap() = Some(location("Boston, MA", &query));
+        let err = service.get_weather(None, false).await.err().unwrap();
+        assert!(err.message.unwrap().ends_with("/gridpoints/BOX/71,76/forecast/hourly"));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_location_errors() {
+        let (url, _) = mock_server(vec![(404, "{}"), (200, r#"{ "properties": {} }"#)]);
+        let config = Config {
+            api_url: Some(format!("{url}/")),
+            ..test_config()
+        };
+        let stats = Stats::default();
+        let resolve =
+            || Service::resolve_location("18.2", "-66.5", &config, &REQWEST_CLIENT, &stats);
+        let err = resolve().await.err().unwrap();
+        assert_eq!(
+            err.message.as_deref(),
+            Some("NWS has no coverage for 18.2,-66.5 (got 404)")
+        );
+        let err = resolve().await.err().unwrap();
+        assert_eq!(
+            err.message.as_deref(),
+            Some("NWS returned malformed zone resolution data")
+        );
+    }
+
+    #[test]
+    fn test_updated() {
+        let properties: ApiForecastProperties = serde_json::from_str(
+            r#"{ "periods": [], "updateTime": "2024-05-01T12:30:00-05:00" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            properties.updated().map(|updated| updated.to_rfc3339()),
+            Some("2024-05-01T17:30:00+00:00".into())
+        );
+
+        let properties: ApiForecastProperties =
+            serde_json::from_str(r#"{ "periods": [], "updateTime": "yesterday" }"#).unwrap();
+        assert_eq!(properties.updated(), None);
+    }
+
+    #[test]
+    fn test_temp_units() {
+        // Whatever unit the API returns, the temperature is shown in the configured one
+        for payload in [FORECAST_SI, FORECAST_US] {
+            let forecast: ApiForecast = serde_json::from_str(payload).unwrap();
+            for (units, temp) in [(UnitSystem::Metric, 25.0), (UnitSystem::Imperial, 77.0)] {
+                let config = Config {
+                    units,
+                    ..test_config()
+                };
+                let moment = forecast.to_moment(&config);
+                let aggregate = forecast.to_aggregate(&config);
+                assert!((moment.temp - temp).abs() < 0.01, "{payload} {units:?}");
+                assert!((aggregate.temp - temp).abs() < 0.01, "{payload} {units:?}");
+                assert_eq!(moment.apparent, aggregate.apparent);
+            }
+        }
+
+        // The apparent temperature is in the configured unit too
+        let forecast: ApiForecast = serde_json::from_str(FORECAST_SI).unwrap();
+        let metric = forecast.to_moment(&test_config());
+        let imperial = forecast.to_moment(&Config {
+            units: UnitSystem::Imperial,
+            ..test_config()
+        });
+        assert!((metric.apparent * 9.0 / 5.0 + 32.0 - imperial.apparent).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_humidity_sanitized() {
+        let with_humidity = |humidity: &str| -> ApiForecast {
+            let payload = FORECAST_SI.replace(
+                r#"{ "unitCode": "wmoUnit:percent", "value": 50 }"#,
+                &format!(r#"{{ "unitCode": "wmoUnit:percent", "value": {humidity} }}"#),
+            );
+            serde_json::from_str(&payload).unwrap()
+        };
+        let config = test_config();
+        let neutral = with_humidity("50").to_moment(&config);
+
+        let missing = with_humidity("null");
+        let moment = missing.to_moment(&config);
+        assert_eq!(moment.humidity, NEUTRAL_HUMIDITY);
+        assert_eq!(moment.apparent, neutral.apparent);
+        assert_eq!(missing.to_aggregate(&config).humidity, NEUTRAL_HUMIDITY);
+
+        assert_eq!(with_humidity("100.4").to_moment(&config).humidity, 100.0);
+        assert_eq!(with_humidity("-0.2").to_aggregate(&config).humidity, 0.0);
+    }
+
+    #[test]
+    fn test_location_cache_path() {
+        let file_name = |config: &Config| {
+            let path = location_cache_path("42.36", "-71.06", config).unwrap();
+            path.file_name().unwrap().to_string_lossy().into_owned()
+        };
+        let config = test_config();
+        assert_eq!(file_name(&config), "points_42.3600_-71.0600_hourly.json");
+        let local = Config {
+            api_url: Some("http://localhost:8080/".into()),
+            ..test_config()
+        };
+        let other = Config {
+            api_url: Some("http://localhost:8081/".into()),
+            ..test_config()
+        };
+        assert_ne!(file_name(&local), file_name(&config));
+        assert_ne!(file_name(&local), file_name(&other));
+    }
+
+    #[test]
+    fn test_jittered_ttl() {
+        let ttl = Duration::from_secs(60 * 60 * 24 * 30);
+        let key = "points_42.3600_-71.0600_hourly.json";
+        let jittered = jittered_ttl(ttl, key);
+        assert_eq!(jittered_ttl(ttl, key), jittered);
+        let (min, max) = (ttl - ttl / 10, ttl + ttl / 10);
+        let others: Vec<Duration> = ["points_40.7128_-74.0060_hourly.json", "points_0_0_daily.json"]
+            .iter()
+            .map(|key| jittered_ttl(ttl, key))
+            .collect();
+        for jittered in std::iter::once(jittered).chain(others.iter().copied()) {
+            assert!(min <= jittered && jittered <= max, "{jittered:?}");
+        }
+        assert!(others.iter().all(|&other| other != jittered));
+        assert_eq!(jittered_ttl(Duration::ZERO, key), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_daily_summary() {
+        let data: ApiForecastResponse = serde_json::from_str(FORECAST_DAILY).unwrap();
+        let periods = data.properties.periods;
+        // In the evening, today's high is over
+        let summary = daily_summary(&periods, TempUnit::Celsius);
+        assert_eq!(summary.today_high, None);
+        assert_eq!(summary.today_low, Some(11.1));
+        assert_eq!(summary.tomorrow_high, Some(25.6));
+        assert_eq!(summary.tomorrow_low, Some(13.9));
+
+        let summary = daily_summary(&periods[1..], TempUnit::Celsius);
+        assert_eq!(summary.today_high, Some(25.6));
+        assert_eq!(summary.today_low, Some(13.9));
+        assert_eq!(summary.tomorrow_high, Some(21.7));
+        assert_eq!(summary.tomorrow_low, Some(8.3));
+
+        let summary = daily_summary(&periods[3..], TempUnit::Fahrenheit);
+        assert!((summary.today_high.unwrap() - 71.06).abs() < 1e-9);
+        assert_eq!(summary.tomorrow_high, None);
+    }
+
+    #[tokio::test]
+    async fn test_daily_forecast_request() {
+        let (url, requests) = mock_server(vec![(200, FORECAST_DAILY)]);
+        let config = test_config();
+        let service = test_service(&config, Vec::new());
+        let hourly_url = format!("{url}/gridpoints/TOP/32,81/forecast/hourly");
+        let periods = service.get_daily_forecast(&hourly_url).await.unwrap();
+        assert_eq!(periods.len(), 5);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        let err = service.get_daily_forecast(&url).await.err().unwrap();
+        assert!(err.message.unwrap().starts_with("No daily forecast known"));
+    }
+
+    #[test]
+    fn test_calm_below() {
+        // 14.8 km/h, or 9.2 mph, from the south-west
+        let forecast: ApiForecast = serde_json::from_str(FORECAST_SI).unwrap();
+        let direction = |units, calm_below| {
+            let config = Config {
+                units,
+                calm_below: Some(calm_below),
+                ..test_config()
+            };
+            let moment = forecast.to_moment(&config);
+            assert_eq!(forecast.to_aggregate(&config).wind_direction, moment.wind_direction);
+            moment.wind_direction
+        };
+        assert_eq!(direction(UnitSystem::Metric, 15.0), None);
+        assert_eq!(direction(UnitSystem::Metric, 14.0), Some(225.0));
+        assert_eq!(direction(UnitSystem::Imperial, 10.0), None);
+        assert_eq!(direction(UnitSystem::Imperial, 9.0), Some(225.0));
+        assert_eq!(test_config().calm_direction(0.5, Some(90.0)), Some(90.0));
+    }
+
+    #[test]
+    fn test_temperature() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let values = [
+            (25.0, "wmoUnit:degC"),
+            (77.0, "wmoUnit:degF"),
+            (298.15, "wmoUnit:K"),
+        ];
+        for (value, unit_code) in values {
+            let temp = Temperature::new(value, unit_code);
+            assert!(close(temp.celsius(), 25.0), "{unit_code}");
+            assert!(close(temp.local(TempUnit::Celsius), 25.0));
+            assert!(close(temp.local(TempUnit::Fahrenheit), 77.0));
+            assert!(close(temp.local(TempUnit::Kelvin), 298.15));
+        }
+        let value: ApiOptionalValue = serde_json::from_str(r#"{ "value": null }"#).unwrap();
+        assert_eq!(value.temperature(), None);
+    }
+
+    #[test]
+    fn test_value_shapes() {
+        let parse = |json| serde_json::from_str::<ApiValue>(json).unwrap();
+        let value = parse(r#"{ "unitCode": "wmoUnit:degC", "value": 25 }"#);
+        assert_eq!((value.value, value.unit_code.as_str()), (25.0, "wmoUnit:degC"));
+        // Ranges are averaged
+        let value = parse(r#"{ "unitCode": "wmoUnit:km_h-1", "minValue": 8, "maxValue": 16 }"#);
+        assert_eq!((value.value, value.unit_code.as_str()), (12.0, "wmoUnit:km_h-1"));
+        // Time series are taken at their first value
+        let value = parse(
+            r#"{
+                "uom": "wmoUnit:degC",
+                "values": [
+                    { "validTime": "2024-06-21T12:00:00+00:00/PT1H", "value": 21 },
+                    { "validTime": "2024-06-21T13:00:00+00:00/PT1H", "value": 22 }
+                ]
+            }"#,
+        );
+        assert_eq!((value.value, value.unit_code.as_str()), (21.0, "wmoUnit:degC"));
+        let value = parse("77");
+        assert_eq!((value.value, value.unit_code.as_str()), (77.0, ""));
+
+        assert!(serde_json::from_str::<ApiValue>(r#"{ "value": null }"#).is_err());
+        assert!(serde_json::from_str::<ApiValue>(r#""77 F""#).is_err());
+        let value: ApiOptionalValue =
+            serde_json::from_str(r#"{ "uom": "", "values": [] }"#).unwrap();
+        assert_eq!(value.value, None);
+    }
+
+    #[test]
+    fn test_mixed_units() {
+        // Every value is converted according to its own unit code
+        let forecast: ApiForecast = serde_json::from_str(
+            r#"{
+                "isDaytime": true,
+                "temperature": { "unitCode": "wmoUnit:degF", "value": 77 },
+                "relativeHumidity": { "unitCode": "wmoUnit:percent", "value": 50 },
+                "dewpoint": { "unitCode": "wmoUnit:K", "value": 287.05 },
+                "windSpeed": { "unitCode": "wmoUnit:m_s-1", "value": 5 },
+                "windGust": { "unitCode": "wmoUnit:kt", "value": 20 },
+                "windDirection": "SW",
+                "shortForecast": "Sunny"
+            }"#,
+        )
+        .unwrap();
+        let moment = forecast.to_moment(&test_config());
+        assert!((moment.temp - 25.0).abs() < 0.01, "{}", moment.temp);
+        assert!((moment.dewpoint.unwrap() - 13.9).abs() < 0.01);
+        assert!((moment.wind - 18.0).abs() < 0.01, "{}", moment.wind);
+        assert!((moment.wind_gust.unwrap() - 37.04).abs() < 0.01);
+
+        let moment = forecast.to_moment(&Config {
+            units: UnitSystem::Imperial,
+            ..test_config()
+        });
+        assert!((moment.temp - 77.0).abs() < 0.01, "{}", moment.temp);
+        assert!((moment.dewpoint.unwrap() - 57.02).abs() < 0.01);
+        assert!((moment.wind - 11.18).abs() < 0.01, "{}", moment.wind);
+        assert!((moment.wind_gust.unwrap() - 23.02).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_apparent_disabled() {
+        let config: Config = toml::from_str("apparent = false").unwrap();
+        for payload in [FORECAST_SI, FORECAST_US] {
+            let forecast: ApiForecast = serde_json::from_str(payload).unwrap();
+            let moment = forecast.to_moment(&config);
+            assert_eq!(moment.apparent, moment.temp);
+            assert_ne!(forecast.to_moment(&test_config()).apparent, moment.temp);
+        }
+    }
+
+    #[test]
+    fn test_kelvin() {
+        let config: Config =
+            toml::from_str("units = \"imperial\"\ntemp_unit = \"kelvin\"").unwrap();
+        for payload in [FORECAST_SI, FORECAST_US] {
+            let forecast: ApiForecast = serde_json::from_str(payload).unwrap();
+            let moment = forecast.to_moment(&config);
+            assert!((moment.temp - 298.15).abs() < 0.01, "{}", moment.temp);
+            assert!((moment.dewpoint.unwrap() - 287.05).abs() < 0.01);
+            let celsius = forecast.to_moment(&test_config());
+            assert!((moment.apparent - 273.15 - celsius.apparent).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_dewpoint() {
+        for payload in [FORECAST_SI, FORECAST_US] {
+            let forecast: ApiForecast = serde_json::from_str(payload).unwrap();
+            let metric = forecast.dewpoint(TempUnit::Celsius).unwrap();
+            let imperial = forecast.dewpoint(TempUnit::Fahrenheit).unwrap();
+            assert!((metric - 13.9).abs() < 0.01, "{metric}");
+            assert!((imperial - 57.02).abs() < 0.01, "{imperial}");
+        }
+    }
+
+    #[test]
+    fn test_missing_dewpoint() {
+        let forecast: ApiForecast = serde_json::from_str(
+            &FORECAST_SI.replace(r#""value": 13.9"#, r#""value": null"#),
+        )
+        .unwrap();
+        assert_eq!(forecast.dewpoint(TempUnit::Celsius), None);
+    }
+
+    #[test]
+    fn test_cycle_location() {
+        let config = test_config();
+        let service = test_service(&config, vec![location("A", ""), location("B", "")]);
+        assert!(service.cycle_location());
+        assert_eq!(service.current.load(Ordering::Relaxed), 1);
+        assert!(service.cycle_location());
+        assert_eq!(service.current.load(Ordering::Relaxed), 0);
+
+        let service = test_service(&config, vec![location("A", "")]);
+        assert!(!service.has_several_locations());
+        assert!(!service.cycle_location());
+        assert_eq!(service.current.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_relative_location() {
+        let location: ApiRelativeLocationProperties = serde_json::from_str(
+            r#"{
+                "city": "Boulder",
+                "state": "CO",
+                "distance": { "unitCode": "wmoUnit:m", "value": 1609.344 },
+                "bearing": { "unitCode": "wmoUnit:degree_(angle)", "value": 45 }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(location.distance.value, Some(1609.344));
+        assert_eq!(location.bearing.value, Some(45.0));
+
+        let location: ApiRelativeLocationProperties =
+            serde_json::from_str(r#"{ "city": "Boulder", "state": "CO" }"#).unwrap();
+        assert_eq!(location.distance.value, None);
+        assert_eq!(location.bearing.value, None);
+    }
+
+    #[test]
+    fn test_api_url() {
+        let config: Config = toml::from_str("api_url = \"http://localhost:8080\"").unwrap();
+        assert_eq!(config.api_url(), "http://localhost:8080/");
+        let config: Config = toml::from_str("api_url = \"https://proxy/nws/\"").unwrap();
+        assert_eq!(config.api_url(), "https://proxy/nws/");
+        assert_eq!(Config::default().api_url(), API_URL);
+
+        assert!(toml::from_str::<Config>("api_url = \"not a url\"").is_err());
+        assert!(toml::from_str::<Config>("api_url = \"ftp://localhost\"").is_err());
+        assert!(toml::from_str::<Config>("api_url = \"http://localhost/?key=1\"").is_err());
+    }
+
+    #[test]
+    fn test_forecast_hours() {
+        let config: Config = toml::from_str("forecast_hours = 1").unwrap();
+        assert_eq!(config.forecast_periods(), 1);
+        let err = toml::from_str::<Config>("forecast_hours = 0").unwrap_err();
+        assert!(err.to_string().contains("'forecast_hours' must be at least 1"));
+    }
+
+    #[test]
+    fn test_gridpoint_urls() {
+        let config = Config::default();
+        assert_eq!(
+            config.gridpoint_forecast_url("BOX", 71, 76),
+            "https://api.weather.gov/gridpoints/BOX/71,76/forecast/hourly"
+        );
+        let config = Config {
+            forecast_mode: ForecastMode::Daily,
+            units: UnitSystem::Imperial,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.gridpoint_forecast_url("BOX", 71, 76),
+            "https://api.weather.gov/gridpoints/BOX/71,76/forecast"
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter {
+            tokens: 0.0,
+            updated: None,
+        };
+        let per_minute = NonZeroU32::new(2).unwrap();
+        let start = Instant::now();
+        assert_eq!(limiter.acquire(per_minute, start), Duration::ZERO);
+        assert_eq!(limiter.acquire(per_minute, start), Duration::ZERO);
+        // The bucket is empty, and refills with a token every 30 seconds
+        assert_eq!(limiter.acquire(per_minute, start), Duration::from_secs(30));
+        assert_eq!(limiter.acquire(per_minute, start), Duration::from_secs(60));
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.acquire(per_minute, later), Duration::from_secs(30));
+        // Idle time doesn't build up more than a minute's worth of requests
+        let much_later = later + Duration::from_secs(600);
+        assert_eq!(limiter.acquire(per_minute, much_later), Duration::ZERO);
+        assert_eq!(limiter.acquire(per_minute, much_later), Duration::ZERO);
+        assert!(!limiter.acquire(per_minute, much_later).is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_gridpoint_location() {
+        let stats = Stats::default();
+        let config: Config = toml::from_str("office = \"box\"\ngrid_x = 71\ngrid_y = 76").unwrap();
+        let location = Service::gridpoint_location(false, &config, &REQWEST_CLIENT, &stats)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(location.name, "BOX 71,76");
+        assert_eq!(location.grid_point.unwrap().office, "BOX");
+
+        let config: Config = toml::from_str("office = \"BOX\"\ngrid_x = 71").unwrap();
+        let location = Service::gridpoint_location(false, &config, &REQWEST_CLIENT, &stats).await;
+        assert!(location.is_err());
+
+        let config: Config = toml::from_str(
+            "office = \"BOX\"\ngrid_x = 71\ngrid_y = 76\ncoordinates = [\"42.36\", \"-71.06\"]",
+        )
+        .unwrap();
+        let location = Service::gridpoint_location(false, &config, &REQWEST_CLIENT, &stats).await;
+        assert!(location.is_err());
+
+        let config = Config::default();
+        let location = Service::gridpoint_location(true, &config, &REQWEST_CLIENT, &stats).await;
+        assert!(location.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_location_name() {
+        let name = |toml| toml::from_str::<Config>(toml).unwrap().location_name("Boston, MA");
+        assert_eq!(name(""), "Boston, MA");
+        assert_eq!(name("location_part = \"city\""), "Boston");
+        assert_eq!(name("location_part = \"state\""), "MA");
+        assert_eq!(name("max_location_len = 10"), "Boston, MA");
+        assert_eq!(name("max_location_len = 5"), "Bost…");
+        assert_eq!(name("location_part = \"city\"\nmax_location_len = 3"), "Bo…");
+        // Grid points don't have a state
+        let config: Config = toml::from_str("location_part = \"state\"").unwrap();
+        assert_eq!(config.location_name("BOX 71,76"), "BOX 71,76");
+        let config: Config = toml::from_str("max_location_len = 7").unwrap();
+        assert_eq!(config.location_name("Mayagüez, PR"), "Mayagü…");
+        assert!(toml::from_str::<Config>("max_location_len = 0").is_err());
+    }
+
+    #[test]
+    fn test_coordinates_config() {
+        let parse = |toml: &str| toml::from_str::<Config>(toml).map(|config| config.coordinates);
+        let coordinates = Some(("42.36".into(), "-71.06".into()));
+        assert_eq!(parse(r#"coordinates = ["42.36", " -71.06"]"#).unwrap(), coordinates);
+        assert_eq!(parse(r#"coordinates = "42.36,-71.06""#).unwrap(), coordinates);
+        assert_eq!(parse(r#"coordinates = "42.36 -71.06""#).unwrap(), coordinates);
+        assert_eq!(parse(r#"coordinates = "42.36, -71.06""#).unwrap(), coordinates);
+
+        let error = |toml| parse(toml).unwrap_err().to_string();
+        assert!(error(r#"coordinates = ["91", "0"]"#).contains("latitude 91 is not between"));
+        assert!(error(r#"coordinates = ["-97.09", "39.75"]"#).contains("swapped"));
+        assert!(error(r#"coordinates = ["42.36", "-181"]"#).contains("longitude -181 is not"));
+        assert!(error(r#"coordinates = ["42.36N", "-71.06"]"#).contains("'42.36N' is not a"));
+        assert!(error(r#"coordinates = ["NaN", "-71.06"]"#).contains("'NaN' is not a"));
+        assert!(error(r#"coordinates = "42.36""#).contains("is not a latitude and longitude"));
+
+        let config: Config = toml::from_str(r#"locations = [["42.36", "-71.06"], "40.71,-74.01"]"#)
+            .unwrap();
+        assert_eq!(config.locations.len(), 2);
+        assert!(toml::from_str::<Config>(r#"locations = [["42.36", "-200"]]"#).is_err());
+        assert!(toml::from_str::<Config>(r#"fallback_coordinates = "100,0""#).is_err());
+    }
+
+    #[test]
+    fn test_feature_flags() {
+        let config = Config::default();
+        assert_eq!(config.feature_flags, DEFAULT_FEATURE_FLAGS);
+        let config: Config = toml::from_str("feature_flags = []").unwrap();
+        assert!(config.feature_flags.is_empty());
+        let config: Config =
+            toml::from_str("feature_flags = [\"forecast_wind_speed_qv\"]").unwrap();
+        assert_eq!(config.feature_flags, ["forecast_wind_speed_qv"]);
+        for invalid in ["", "a,b", "a b"] {
+            let err = toml::from_str::<Config>(&format!("feature_flags = [\"{invalid}\"]"))
+                .unwrap_err();
+            assert!(err.to_string().contains("not a valid feature flag"), "{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_station() {
+        let stats = Stats::default();
+        let config: Config = toml::from_str("station = \" kbos \"").unwrap();
+        assert_eq!(config.station.as_deref(), Some("KBOS"));
+        let config: Config = toml::from_str("station = \"LOXC1\"").unwrap();
+        assert_eq!(config.station.as_deref(), Some("LOXC1"));
+        for invalid in ["", "LOXC12", "K-OS"] {
+            let err = toml::from_str::<Config>(&format!("station = \"{invalid}\"")).unwrap_err();
+            assert!(err.to_string().contains("not a valid station identifier"), "{err}");
+        }
+
+        let (url, _) = mock_server(vec![
+            (
+                200,
+                r#"{
+                    "geometry": { "type": "Point", "coordinates": [-71.00972, 42.36056] },
+                    "properties": { "stationIdentifier": "KBOS" }
+                }"#,
+            ),
+            (404, "{}"),
+        ]);
+        let config = Config {
+            api_url: Some(format!("{url}/")),
+            ..test_config()
+        };
+        let coordinates =
+            Service::station_coordinates("KBOS", &config, &REQWEST_CLIENT, &stats).await;
+        assert_eq!(coordinates.unwrap(), (42.36056, -71.00972));
+        let err = Service::station_coordinates("KXYZ", &config, &REQWEST_CLIENT, &stats)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.message.as_deref(), Some("Unknown station KXYZ"));
+
+        let config: Config =
+            toml::from_str("station = \"KBOS\"\ncoordinates = [\"42.36\", \"-71.06\"]").unwrap();
+        assert!(Service::station_location(false, &config, &REQWEST_CLIENT, &stats).await.is_err());
+        let config: Config = toml::from_str("station = \"KBOS\"").unwrap();
+        assert!(Service::station_location(true, &config, &REQWEST_CLIENT, &stats).await.is_err());
+        let config = Config::default();
+        let location = Service::station_location(false, &config, &REQWEST_CLIENT, &stats).await;
+        assert!(location.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_request_urls() {
+        let config = Config::default();
+        assert_eq!(
+            config.points_url("39.7456", "-97.0892"),
+            "https://api.weather.gov/points/39.7456,-97.0892"
+        );
+        assert_eq!(
+            config.alerts_url("39.7456,-97.0892"),
+            "https://api.weather.gov/alerts/active?point=39.7456,-97.0892"
+        );
+
+        assert_eq!(
+            config.points_url("39.745612345", " -97.08921 "),
+            "https://api.weather.gov/points/39.7456,-97.0892"
+        );
+        assert_eq!(
+            config.points_url("39.7", "-97"),
+            "https://api.weather.gov/points/39.7,-97"
+        );
+
+        let config: Config = toml::from_str("api_url = \"http://localhost:8080\"").unwrap();
+        assert_eq!(
+            config.points_url("39.7456", "-97.0892"),
+            "http://localhost:8080/points/39.7456,-97.0892"
+        );
+    }
+
+    /// A location named `name`, with its forecast at `query`
+    fn location(name: &str, query: &str) -> LocationInfo {
+        LocationInfo {
+            query: query.into(),
+            name: name.into(),
+            distance: None,
+            bearing: None,
+            point: String::new(),
+            station: None,
+            grid_point: None,
+        }
+    }
+
+    fn test_service<'a>(config: &'a Config, locations: Vec<LocationInfo>) -> Service<'a> {
+        Service {
+            config,
+            client: REQWEST_CLIENT.clone(),
+            locations,
+            current: AtomicUsize::new(0),
+            fallback: Mutex::new(None),
+            autolocated: Mutex::new(None),
+            stats: Stats::default(),
+        }
+    }
+
+    fn mock_service<'a>(config: &'a Config, url: &str) -> Service<'a> {
+        test_service(config, vec![location("Test", url)])
+    }
+
+    #[tokio::test]
+    async fn test_alerts_failure() {
+        let (url, requests) = mock_server(vec![(200, FORECAST_HOURLY), (404, "{}")]);
+        let config = Config {
+            api_url: Some(format!("{url}/")),
+            show_alerts: true,
+            ..test_config()
+        };
+        let weather = mock_service(&config, &url)
+            .get_weather(None, false)
+            .await
+            .unwrap();
+        assert_eq!(weather.current_weather.temp, 22.2);
+        assert!(weather.alerts.is_none());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_block_does_not_retry() {
+        // Any request after the first one would be answered, and counted
+        let (url, requests) = mock_server(vec![(404, "{}"); 5]);
+        let config = test_config();
+        let providers: [(&'static str, Box<dyn WeatherProvider + Send + Sync>); 1] =
+            [("nws", Box::new(mock_service(&config, &url)))];
+        let result =
+            crate::blocks::weather::get_weather(&providers, Duration::from_secs(10), None, false)
+                .await;
+        assert!(result.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_empty_forecast() {
+        let (url, _) = mock_server(vec![(200, r#"{ "properties": { "periods": [] } }"#)]);
+        let config = test_config();
+        let err = mock_service(&config, &url)
+            .get_weather(None, true)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message.as_deref(),
+            Some("No forecast periods received, expected 12")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_short_forecast() {
+        let body = format!(r#"{{ "properties": {{ "periods": [{FORECAST_SI}, {FORECAST_SI}] }} }}"#);
+        let (url, _) = mock_server(vec![(200, Box::leak(body.into_boxed_str()))]);
+        let config = test_config();
+        let weather = mock_service(&config, &url)
+            .get_weather(None, true)
+            .await
+            .unwrap();
+        assert_eq!(weather.periods.len(), 1);
+        let forecast = weather.forecast.unwrap();
+        assert_eq!(forecast.avg.temp, 25.0);
+        assert_eq!(forecast.fin.unwrap().temp, 25.0);
+    }
+
+    #[test]
+    fn test_speed() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let kmh = Speed::new(14.8, "wmoUnit:km_h-1");
+        assert!(close(kmh.kmh(), 14.8));
+        assert!(close(kmh.local(UnitSystem::Metric), 14.8));
+        assert!(close(kmh.local(UnitSystem::Imperial), 14.8 / MPH_TO_KPH));
+
+        let mph = Speed::new(9.2, "wmoUnit:mi_h-1");
+        assert!(close(mph.kmh(), 9.2 * MPH_TO_KPH));
+        assert!(close(mph.local(UnitSystem::Imperial), 9.2));
+
+        let ms = Speed::new(5.0, "wmoUnit:m_s-1");
+        assert!(close(ms.ms(), 5.0));
+        assert!(close(ms.kmh(), 18.0));
+    }
+
+    #[tokio::test]
+    async fn test_forecast_offset() {
+        let later = FORECAST_SI.replace("25.0", "10.0");
+        let body = format!(
+            r#"{{ "properties": {{ "periods": [{FORECAST_SI}, {later}, {later}] }} }}"#
+        );
+        let (url, _) = mock_server(vec![(200, Box::leak(body.into_boxed_str()))]);
+        let config = Config {
+            forecast_hours: 1,
+            forecast_offset: 1,
+            ..test_config()
+        };
+        let weather = mock_service(&config, &url)
+            .get_weather(None, true)
+            .await
+            .unwrap();
+        assert_eq!(weather.current_weather.temp, 25.0);
+        let forecast = weather.forecast.unwrap();
+        assert_eq!(forecast.avg.temp, 10.0);
+        assert_eq!(forecast.fin.unwrap().temp, 10.0);
+    }
+
+    #[test]
+    fn test_current_period() {
+        let data: ApiForecastResponse = serde_json::from_str(FORECAST_HOURLY).unwrap();
+        let data = data.properties.periods;
+        let at = |time| parse_time(time).unwrap();
+        let index = |mode: CurrentPeriod, time| mode.index(&data, at(time));
+        assert_eq!(index(CurrentPeriod::First, "2024-05-01T15:30:00-05:00"), 0);
+        assert_eq!(index(CurrentPeriod::NearestToNow, "2024-05-01T15:30:00-05:00"), 1);
+        assert_eq!(index(CurrentPeriod::NearestToNow, "2024-05-01T16:00:00-05:00"), 2);
+        // Before the first period and after the last one, the closest one is used
+        assert_eq!(index(CurrentPeriod::NearestToNow, "2024-05-01T10:00:00-05:00"), 0);
+        assert_eq!(
+            index(CurrentPeriod::NearestToNow, "2025-01-01T00:00:00-05:00"),
+            data.len() - 1
+        );
+        assert_eq!(CurrentPeriod::NearestToNow.index(&[], Utc::now()), 0);
+    }
+
+    #[tokio::test]
+    async fn test_periods_after_current() {
+        // All the periods of the fixture have ended, so the last one is the nearest to now
+        let (url, _) = mock_server(vec![(200, FORECAST_HOURLY)]);
+        let config = Config {
+            forecast_hours: 2,
+            current_period: CurrentPeriod::NearestToNow,
+            ..test_config()
+        };
+        let weather = mock_service(&config, &url)
+            .get_weather(None, true)
+            .await
+            .unwrap();
+        assert_eq!(weather.current_weather.temp, 23.3);
+        assert!(weather.periods.is_empty());
+        let forecast = weather.forecast.unwrap();
+        assert_eq!(forecast.avg.temp, 23.3);
+        assert_eq!(forecast.fin.unwrap().temp, 23.3);
+    }
+
+    #[tokio::test]
+    async fn test_final_period() {
+        let later = FORECAST_SI.replace("25.0", "10.0");
+        let body = format!(r#"{{ "properties": {{ "periods": [{FORECAST_SI}, {later}] }} }}"#);
+        let body: &'static str = Box::leak(body.into_boxed_str());
+        let (url, _) = mock_server(vec![(200, body); 3]);
+        for (final_period, fin) in [
+            (FinalPeriod::Horizon, Some(10.0)),
+            (FinalPeriod::LastAggregated, Some(25.0)),
+            (FinalPeriod::Omitted, None),
+        ] {
+            let config = Config {
+                forecast_hours: 1,
+                final_period,
+                ..test_config()
+            };
+            let weather = mock_service(&config, &url)
+                .get_weather(None, true)
+                .await
+                .unwrap();
+            let forecast = weather.forecast.unwrap();
+            assert_eq!(forecast.fin.map(|fin| fin.temp), fin, "{final_period:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forecast_offset_too_large() {
+        let body = format!(r#"{{ "properties": {{ "periods": [{FORECAST_SI}, {FORECAST_SI}] }} }}"#);
+        let (url, _) = mock_server(vec![(200, Box::leak(body.into_boxed_str()))]);
+        let config = Config {
+            forecast_offset: 2,
+            ..test_config()
+        };
+        let err = mock_service(&config, &url)
+            .get_weather(None, true)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.message.as_deref(),
+            Some("forecast_offset is 2, but only 2 periods are available")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_server_error() {
+        let stats = Stats::default();
+        let (url, requests) = mock_server(vec![(503, ""), (503, ""), (200, "{}")]);
+        let response = send_request(&test_config(), &stats, || REQWEST_CLIENT.get(&url)).await;
+        assert!(response.is_ok());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let stats = Stats::default();
+        let (url, requests) = mock_server(vec![(500, ""); 4]);
+        let response = send_request(&test_config(), &stats, || REQWEST_CLIENT.get(&url)).await;
+        assert!(response.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let stats = Stats::default();
+        let (url, requests) = mock_server(vec![(404, ""), (200, "{}")]);
+        let response = send_request(&test_config(), &stats, || REQWEST_CLIENT.get(&url)).await;
+        assert_eq!(
+            response.unwrap_err().status(),
+            Some(reqwest::StatusCode::NOT_FOUND)
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let stats = Stats::default();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let config = Config {
+            timeout: Seconds(Duration::from_millis(50)),
+            retries: 0,
+            ..test_config()
+        };
+        // The connection is accepted by the OS, but nothing ever answers it
+        let err = send_request(&config, &stats, || REQWEST_CLIENT.get(&url))
+            .await
+            .map_err(request_error("weather request"))
+            .unwrap_err();
+        assert_eq!(err.message.as_deref(), Some("weather request timed out"));
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn test_request_refused() {
+        let stats = Stats::default();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let config = Config {
+            retries: 0,
+            ..test_config()
+        };
+        let err = send_request(&config, &stats, || REQWEST_CLIENT.get(&url))
+            .await
+            .map_err(request_error("weather request"))
+            .unwrap_err();
+        assert_eq!(err.message.as_deref(), Some("weather request failed"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_connect_error() {
+        let stats = Stats::default();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let url = format!("http://{addr}");
+        drop(listener);
+        // The server only comes up after the first attempt failed
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let listener = TcpListener::bind(addr).unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 4096]).unwrap();
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}")
+                .unwrap();
+        });
+        let config = Config {
+            retries: 0,
+            connect_retry_delay: Seconds(Duration::from_millis(300)),
+            ..test_config()
+        };
+        let response = send_request(&config, &stats, || REQWEST_CLIENT.get(&url)).await;
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    fn test_short_forecast_to_icon() {
+        let cases = [
+            ("Sunny", "weather_sun"),
+            ("Mostly Sunny", "weather_sun"),
+            ("Clear", "weather_moon"),
+            ("Mostly Clear", "weather_moon"),
+            ("Partly Cloudy", "weather_partly_cloudy"),
+            ("Mostly Cloudy", "weather_partly_cloudy"),
+            ("Partly Sunny", "weather_partly_cloudy"),
+            ("Few Clouds", "weather_partly_cloudy"),
+            ("Scattered Clouds", "weather_partly_cloudy"),
+            ("Cloudy", "weather_clouds"),
+            ("Overcast", "weather_clouds"),
+            ("Patchy Fog", "weather_fog"),
+            ("Areas Of Freezing Fog", "weather_fog"),
+            ("Mist", "weather_fog"),
+            ("Slight Chance Light Rain", "weather_rain"),
+            ("Rain Showers Likely", "weather_rain"),
+            ("Drizzle", "weather_rain"),
+            ("Chance Showers And Thunderstorms", "weather_thunder"),
+            ("Severe Thunderstorms", "weather_thunder"),
+            ("Light Snow Likely", "weather_snow"),
+            ("Snow Showers", "weather_snow"),
+            ("Rain And Snow", "weather_snow"),
+            ("Flurries", "weather_snow"),
+            ("Blizzard", "weather_snow"),
+            ("Sleet", "weather_sleet"),
+            ("Chance Snow And Sleet", "weather_sleet"),
+            ("Rain And Sleet", "weather_sleet"),
+            ("Freezing Rain", "weather_sleet"),
+            ("Freezing Drizzle", "weather_sleet"),
+            ("Rain And Freezing Rain", "weather_sleet"),
+            ("Ice Pellets", "weather_sleet"),
+            ("Hail", "weather_sleet"),
+            ("Haze", "weather_default"),
+        ];
+        for (forecast, icon) in cases {
+            let is_night = icon == "weather_moon";
+            assert_eq!(
+                short_forecast_to_icon(forecast, is_night).to_icon_str(),
+                icon,
+                "{forecast}"
+            );
+        }
+        assert_eq!(
+            short_forecast_to_icon("Freezing Rain", true).to_icon_str(),
+            "weather_sleet_night"
+        );
+    }
+
+    #[test]
+    fn test_api_icon_to_icon() {
+        let cases = [
+            (
+                "https://api.weather.gov/icons/land/day/tsra,40?size=medium",
+                Some("weather_thunder"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/night/sct?size=small",
+                Some("weather_partly_cloudy_night"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/night/rain_showers,30/tsra_hi,40?size=small",
+                Some("weather_rain_night"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/day/wind_ovc?size=small",
+                Some("weather_clouds"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/day/fzra,70?size=small",
+                Some("weather_sleet"),
+            ),
+            (
+                "https://api.weather.gov/icons/land/night/skc?size=small",
+                Some("weather_moon"),
+            ),
+            ("https://api.weather.gov/icons/land/day/unknown?size=small", None),
+            ("https://api.weather.gov/icons/land/dusk/skc", None),
+            ("https://example.com/sunny.png", None),
+        ];
+        for (url, icon) in cases {
+            assert_eq!(api_icon_to_icon(url).map(WeatherIcon::to_icon_str), icon, "{url}");
+        }
+
+        let mut forecast: ApiForecast = serde_json::from_str(FORECAST_SI).unwrap();
+        forecast.icon = Some("https://api.weather.gov/icons/land/day/snow,20?size=small".into());
+        let config = Config {
+            use_api_icon: true,
+            ..test_config()
+        };
+        assert_eq!(forecast.to_moment(&config).icon.to_icon_str(), "weather_snow");
+        assert_ne!(forecast.to_moment(&test_config()).icon.to_icon_str(), "weather_snow");
+        // Falls back to the short forecast if the icon can't be parsed
+        forecast.icon = Some("https://api.weather.gov/icons/land/day/unknown".into());
+        assert_eq!(
+            forecast.to_moment(&config).icon.to_icon_str(),
+            forecast.to_moment(&test_config()).icon.to_icon_str()
+        );
+    }
+
+    #[test]
+    fn test_observation() {
+        let observation: ApiObservationProperties = serde_json::from_str(
+            r#"{
+                "textDescription": "Light Rain",
+                "temperature": { "unitCode": "wmoUnit:degC", "value": 10.0 },
+                "dewpoint": { "unitCode": "wmoUnit:degC", "value": null },
+                "relativeHumidity": { "unitCode": "wmoUnit:percent", "value": 90.0 },
+                "windDirection": { "unitCode": "wmoUnit:degree_(angle)", "value": 270 },
+                "windSpeed": { "unitCode": "wmoUnit:km_h-1", "value": 16.09344 },
+                "windGust": { "unitCode": "wmoUnit:km_h-1", "value": null },
+                "barometricPressure": { "unitCode": "wmoUnit:Pa", "value": 101320 }
+            }"#,
+        )
+        .unwrap();
+        let forecast: ApiForecast = serde_json::from_str(FORECAST_US).unwrap();
+        let config = Config {
+            units: UnitSystem::Imperial,
+            ..test_config()
+        };
+
+        let moment = observation.to_moment(&config, forecast.to_moment(&config), true);
+        assert_eq!(moment.icon.to_icon_str(), "weather_rain_night");
+        assert_eq!(moment.weather_verbose, "Light Rain");
+        assert!((moment.temp - 50.0).abs() < 0.01);
+        assert_eq!(moment.humidity, 90.0);
+        // Missing observations fall back to the forecast
+        assert!((moment.dewpoint.unwrap() - 57.02).abs() < 0.01);
+        assert!((moment.wind - 10.0).abs() < 0.01);
+        assert_eq!(moment.wind_direction, Some(270.0));
+        assert!((moment.pressure.unwrap() - 29.92).abs() < 0.01);
+        assert_eq!(moment.precip_prob, Some(0.0));
+    }
+
+    #[test]
+    fn test_couple_apparent() {
+        let config = test_config();
+        let forecast: ApiForecast = serde_json::from_str(FORECAST_SI).unwrap();
+        let aggregate = |temp, apparent| ForecastAggregate {
+            temp,
+            apparent,
+            ..forecast.to_aggregate(&config)
+        };
+        // The coldest period is not the one that feels the coldest
+        let data = [
+            aggregate(10.0, 4.0),
+            aggregate(8.0, 6.0),
+            aggregate(20.0, 22.0),
+            aggregate(18.0, 23.0),
+        ];
+
+        let combine = |couple_apparent| {
+            let fin = forecast.to_moment(&config);
+            combine_forecasts(
+                &data,
+                Some(fin),
+                Aggregate::Mean,
+                Weighting::Uniform,
+                couple_apparent,
+                Extrema::PerField,
+            )
+        };
+        let independent = combine(false);
+        assert_eq!(independent.min.temp, 8.0);
+        assert_eq!(independent.min.apparent, 4.0);
+        assert_eq!(independent.max.temp, 20.0);
+        assert_eq!(independent.max.apparent, 23.0);
+
+        let coupled = combine(true);
+        assert_eq!(coupled.min.temp, 8.0);
+        assert_eq!(coupled.min.apparent, 6.0);
+        assert_eq!(coupled.max.temp, 20.0);
+        assert_eq!(coupled.max.apparent, 22.0);
+    }
+}
@@ -0,0 +1,389 @@
+//! Support for the [Open-Meteo](https://open-meteo.com/) forecast API.
+//!
+//! The API is free for non-commercial use and needs no API key. Weather conditions are reported
+//! as [WMO weather codes](https://open-meteo.com/en/docs#weathervariables), which are translated
+//! into the set of supported icons.
+
+use super::*;
+
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+const HOURLY_VARIABLES: &str = "temperature_2m,relative_humidity_2m,dew_point_2m,\
+apparent_temperature,precipitation_probability,weather_code,pressure_msl,wind_speed_10m,\
+wind_direction_10m,wind_gusts_10m,visibility,is_day";
+
+#[derive(Deserialize, Debug, SmartDefault)]
+#[serde(tag = "name", rename_all = "lowercase", deny_unknown_fields, default)]
+pub struct Config {
+    coordinates: Option<(String, String)>,
+    #[default(12)]
+    forecast_hours: usize,
+    #[serde(default)]
+    units: UnitSystem,
+    pressure_unit: Option<PressureUnit>,
+    #[serde(default)]
+    aggregate: Aggregate,
+    #[serde(default)]
+    aggregate_weighting: Weighting,
+    #[serde(default)]
+    final_period: FinalPeriod,
+    #[serde(default)]
+    extrema_mode: Extrema,
+}
+
+pub(super) struct Service<'a> {
+    config: &'a Config,
+    client: reqwest::Client,
+}
+
+impl Config {
+    pub(super) fn has_coordinates(&self) -> bool {
+        self.coordinates.is_some()
+    }
+
+    /// The unit pressures are shown in, by default the one of the unit system
+    fn pressure_unit(&self) -> PressureUnit {
+        self.pressure_unit.unwrap_or(self.units.pressure_unit())
+    }
+}
+
+impl<'a> Service<'a> {
+    pub(super) fn new(config: &'a Config, client: reqwest::Client) -> Service<'a> {
+        Self { config, client }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiResponse {
+    hourly: ApiHourly,
+}
+
+/// Hourly values, one entry per hour starting with the current one
+#[derive(Deserialize, Debug)]
+struct ApiHourly {
+    /// Start of each hour, in UTC
+    #[serde(default)]
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f64>>,
+    relative_humidity_2m: Vec<Option<f64>>,
+    dew_point_2m: Vec<Option<f64>>,
+    apparent_temperature: Vec<Option<f64>>,
+    precipitation_probability: Vec<Option<f64>>,
+    weather_code: Vec<Option<u8>>,
+    pressure_msl: Vec<Option<f64>>,
+    wind_speed_10m: Vec<Option<f64>>,
+    wind_direction_10m: Vec<Option<f64>>,
+    wind_gusts_10m: Vec<Option<f64>>,
+    /// Visibility in meters
+    #[serde(default)]
+    visibility: Vec<Option<f64>>,
+    is_day: Vec<Option<u8>>,
+}
+
+impl ApiHourly {
+    fn len(&self) -> usize {
+        self.temperature_2m.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.temperature_2m.is_empty()
+    }
+
+    fn start(&self, i: usize) -> Option<DateTime<Utc>> {
+        let time = self.time.get(i)?;
+        let time = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M").ok()?;
+        Some(time.and_utc())
+    }
+
+    fn moment(&self, i: usize, config: &Config) -> WeatherMoment {
+        let units = config.units;
+        let get = |values: &[Option<f64>]| values.get(i).copied().flatten();
+        let to_kmh = |speed: f64| match units {
+            UnitSystem::Metric => speed,
+            UnitSystem::Imperial => speed * MPH_TO_KPH,
+        };
+
+        let temp = get(&self.temperature_2m).unwrap_or_default();
+        let wind_kmh = to_kmh(get(&self.wind_speed_10m).unwrap_or_default());
+        let wind_gust = get(&self.wind_gusts_10m);
+        let is_night = self.is_day.get(i).copied().flatten() == Some(0);
+        let code = self.weather_code.get(i).copied().flatten();
+        let icon = wmo_code_to_icon(code, is_night);
+
+        WeatherMoment {
+            icon,
+            weather: icon.to_word().into(),
+            weather_verbose: wmo_code_description(code).into(),
+            weather_detailed: None,
+            temp,
+            apparent: get(&self.apparent_temperature).unwrap_or(temp),
+            humidity: get(&self.relative_humidity_2m).unwrap_or_default(),
+            dewpoint: get(&self.dew_point_2m),
+            pressure: get(&self.pressure_msl).map(|hpa| config.pressure_unit().convert_hpa(hpa)),
+            visibility: get(&self.visibility).map(|meters| units.distance_from_meters(meters)),
+            wind: units.speed_from_kmh(wind_kmh),
+            wind_kmh,
+            wind_mph: wind_kmh / MPH_TO_KPH,
+            wind_direction: get(&self.wind_direction_10m),
+            wind_gust,
+            wind_gust_kmh: wind_gust.map(to_kmh),
+            precip_prob: get(&self.precipitation_probability),
+        }
+    }
+}
+
+impl From<&WeatherMoment> for ForecastAggregate {
+    fn from(moment: &WeatherMoment) -> Self {
+        Self {
+            temp: moment.temp,
+            apparent: moment.apparent,
+            humidity: moment.humidity,
+            dewpoint: moment.dewpoint,
+            pressure: moment.pressure,
+            visibility: moment.visibility,
+            wind: moment.wind,
+            wind_kmh: moment.wind_kmh,
+            wind_mph: moment.wind_mph,
+            wind_direction: moment.wind_direction,
+            wind_gust: moment.wind_gust,
+            wind_gust_kmh: moment.wind_gust_kmh,
+            precip_prob: moment.precip_prob,
+            start: None,
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Service<'_> {
+    async fn get_weather(
+        &self,
+        location: Option<&Coordinates>,
+        need_forecast: bool,
+    ) -> Result<WeatherResult> {
+        let (lat, lon) = location
+            .as_ref()
+            .map(|loc| (loc.latitude.to_string(), loc.longitude.to_string()))
+            .or_else(|| self.config.coordinates.clone())
+            .error("No location given")?;
+
+        let (temperature_unit, wind_speed_unit) = match self.config.units {
+            UnitSystem::Metric => ("celsius", "kmh"),
+            UnitSystem::Imperial => ("fahrenheit", "mph"),
+        };
+        // The first hour is the current one
+        let forecast_hours = (self.config.forecast_hours + 1).to_string();
+
+        let data: ApiResponse = self
+            .client
+            .get(FORECAST_URL)
+            .query(&[
+                ("latitude", lat.trim()),
+                ("longitude", lon.trim()),
+                ("hourly", HOURLY_VARIABLES),
+                ("temperature_unit", temperature_unit),
+                ("wind_speed_unit", wind_speed_unit),
+                ("forecast_hours", &forecast_hours),
+            ])
+            .send()
+            .await
+            .error("Forecast request failed")?
+            .json()
+            .await
+            .error("Failed to parse forecast")?;
+
+        let hourly = data.hourly;
+        if hourly.is_empty() {
+            return Err(Error::new("No forecast available"));
+        }
+        let units = self.config.units;
+
+        let forecast = if !need_forecast || self.config.forecast_hours == 0 {
+            None
+        } else {
+            let last = self.config.forecast_hours.min(hourly.len() - 1);
+            let data_agg: Vec<ForecastAggregate> = (0..last)
+                .map(|i| ForecastAggregate {
+                    start: hourly.start(i),
+                    ..ForecastAggregate::from(&hourly.moment(i, self.config))
+                })
+                .collect();
+            let fin = self
+                .config
+                .final_period
+                .index(0, last, hourly.len())
+                .map(|i| hourly.moment(i, self.config));
+            Some(combine_forecasts(
+                &data_agg,
+                fin,
+                self.config.aggregate,
+                self.config.aggregate_weighting,
+                false,
+                self.config.extrema_mode,
+            ))
+        };
+
+        Ok(WeatherResult {
+            location: location.map_or("Unknown".to_string(), |c| c.city.clone()),
+            location_distance: None,
+            location_bearing: None,
+            current_weather: hourly.moment(0, self.config),
+            forecast,
+            alerts: None,
+            updated: None,
+            periods: Vec::new(),
+            coordinates: parse_coordinates(&lat, &lon),
+            temp_unit: units.temp_unit(),
+            pressure_unit: self.config.pressure_unit(),
+            units,
+            provider: "openmeteo",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
+        })
+    }
+}
+
+/// Translate a WMO weather code into an icon
+fn wmo_code_to_icon(code: Option<u8>, is_night: bool) -> WeatherIcon {
+    match code {
+        Some(0 | 1) => WeatherIcon::Clear { is_night },
+        Some(2) => WeatherIcon::PartlyCloudy { is_night },
+        Some(3) => WeatherIcon::Clouds { is_night },
+        Some(45 | 48) => WeatherIcon::Fog { is_night },
+        Some(51 | 53 | 55 | 61 | 63 | 65 | 80 | 81 | 82) => WeatherIcon::Rain { is_night },
+        Some(56 | 57 | 66 | 67) => WeatherIcon::Sleet { is_night },
+        Some(71 | 73 | 75 | 77 | 85 | 86) => WeatherIcon::Snow { is_night },
+        Some(95 | 96 | 99) => WeatherIcon::Thunder { is_night },
+        _ => WeatherIcon::Default,
+    }
+}
+
+/// Describe a WMO weather code, using the wording of the Open-Meteo documentation
+fn wmo_code_description(code: Option<u8>) -> &'static str {
+    match code {
+        Some(0) => "Clear sky",
+        Some(1) => "Mainly clear",
+        Some(2) => "Partly cloudy",
+        Some(3) => "Overcast",
+        Some(45) => "Fog",
+        Some(48) => "Depositing rime fog",
+        Some(51) => "Light drizzle",
+        Some(53) => "Moderate drizzle",
+        Some(55) => "Dense drizzle",
+        Some(56) => "Light freezing drizzle",
+        Some(57) => "Dense freezing drizzle",
+        Some(61) => "Slight rain",
+        Some(63) => "Moderate rain",
+        Some(65) => "Heavy rain",
+        Some(66) => "Light freezing rain",
+        Some(67) => "Heavy freezing rain",
+        Some(71) => "Slight snow fall",
+        Some(73) => "Moderate snow fall",
+        Some(75) => "Heavy snow fall",
+        Some(77) => "Snow grains",
+        Some(80) => "Slight rain showers",
+        Some(81) => "Moderate rain showers",
+        Some(82) => "Violent rain showers",
+        Some(85) => "Slight snow showers",
+        Some(86) => "Heavy snow showers",
+        Some(95) => "Thunderstorm",
+        Some(96) => "Thunderstorm with slight hail",
+        Some(99) => "Thunderstorm with heavy hail",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = r#"{
+        "latitude": 52.52,
+        "longitude": 13.419998,
+        "hourly": {
+            "time": ["2024-05-01T12:00", "2024-05-01T13:00", "2024-05-01T14:00"],
+            "temperature_2m": [18.0, 20.0, null],
+            "relative_humidity_2m": [50, 40, 45],
+            "dew_point_2m": [7.4, 6.0, null],
+            "apparent_temperature": [17.1, 19.5, 18.0],
+            "precipitation_probability": [0, 10, 20],
+            "weather_code": [1, 61, 95],
+            "pressure_msl": [1013.0, 1012.0, 1011.0],
+            "wind_speed_10m": [7.2, 14.4, 21.6],
+            "wind_direction_10m": [180, 270, 90],
+            "wind_gusts_10m": [18.0, null, 32.4],
+            "visibility": [24140.0, null, 1609.344],
+            "is_day": [1, 1, 0]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_hourly() {
+        let hourly = serde_json::from_str::<ApiResponse>(RESPONSE)
+            .unwrap()
+            .hourly;
+        assert_eq!(hourly.len(), 3);
+        assert_eq!(
+            hourly.start(1).map(|start| start.to_rfc3339()),
+            Some("2024-05-01T13:00:00+00:00".into())
+        );
+
+        let metric = Config::default();
+        let imperial = Config {
+            units: UnitSystem::Imperial,
+            ..Config::default()
+        };
+        let current = hourly.moment(0, &metric);
+        assert_eq!(current.weather, "Clear");
+        assert_eq!(current.weather_verbose, "Mainly clear");
+        assert_eq!(current.temp, 18.0);
+        assert_eq!(current.apparent, 17.1);
+        assert_eq!(current.dewpoint, Some(7.4));
+        assert_eq!(current.wind, 7.2);
+        assert_eq!(current.wind_kmh, 7.2);
+        assert!((current.wind_mph - 4.47).abs() < 0.01);
+        assert_eq!(current.wind_gust_kmh, Some(18.0));
+        assert_eq!(current.visibility, Some(24.14));
+        assert_eq!(hourly.moment(1, &metric).visibility, None);
+
+        let later = hourly.moment(2, &imperial);
+        assert_eq!(later.icon.to_icon_str(), "weather_thunder_night");
+        assert_eq!(later.temp, 0.0);
+        assert_eq!(later.dewpoint, None);
+        assert!((later.pressure.unwrap() - 29.85).abs() < 0.01);
+        let mmhg = Config {
+            pressure_unit: Some(PressureUnit::Mmhg),
+            ..imperial
+        };
+        assert!((hourly.moment(2, &mmhg).pressure.unwrap() - 758.31).abs() < 0.01);
+        assert!((later.visibility.unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_wmo_codes() {
+        for (code, icon) in [
+            (0, "weather_sun"),
+            (2, "weather_partly_cloudy"),
+            (3, "weather_clouds"),
+            (48, "weather_fog"),
+            (53, "weather_rain"),
+            (57, "weather_sleet"),
+            (66, "weather_sleet"),
+            (75, "weather_snow"),
+            (82, "weather_rain"),
+            (86, "weather_snow"),
+            (99, "weather_thunder"),
+            (42, "weather_default"),
+        ] {
+            assert_eq!(
+                wmo_code_to_icon(Some(code), false).to_icon_str(),
+                icon,
+                "{code}"
+            );
+        }
+        assert_eq!(
+            wmo_code_to_icon(None, true).to_icon_str(),
+            "weather_default"
+        );
+    }
+}
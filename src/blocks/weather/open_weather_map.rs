@@ -49,16 +49,27 @@ where
     })
 }
 
+impl Config {
+    pub(super) fn has_coordinates(&self) -> bool {
+        self.coordinates.is_some()
+    }
+}
+
 pub(super) struct Service<'a> {
     api_key: &'a String,
     units: &'a UnitSystem,
     lang: &'a String,
     location_query: Option<String>,
     forecast_hours: usize,
+    client: reqwest::Client,
 }
 
 impl<'a> Service<'a> {
-    pub(super) async fn new(autolocate: bool, config: &'a Config) -> Result<Service<'a>> {
+    pub(super) async fn new(
+        autolocate: bool,
+        config: &'a Config,
+        client: reqwest::Client,
+    ) -> Result<Service<'a>> {
         let api_key = config.api_key.as_ref().or_error(|| {
             format!("missing key 'service.api_key' and environment variable {API_KEY_ENV}",)
         })?;
@@ -66,8 +77,10 @@ impl<'a> Service<'a> {
             api_key,
             units: &config.units,
             lang: &config.lang,
-            location_query: Service::get_location_query(autolocate, api_key, config).await?,
+            location_query: Service::get_location_query(autolocate, api_key, config, &client)
+                .await?,
             forecast_hours: config.forecast_hours,
+            client,
         })
     }
 
@@ -75,6 +88,7 @@ impl<'a> Service<'a> {
         autolocate: bool,
         api_key: &String,
         config: &Config,
+        client: &reqwest::Client,
     ) -> Result<Option<String>> {
         if autolocate {
             return Ok(None);
@@ -92,7 +106,7 @@ impl<'a> Service<'a> {
                 Some(place) => {
                     let url = format!("{GEO_URL}/direct?q={place}&appid={api_key}");
 
-                    REQWEST_CLIENT
+                    client
                         .get(url)
                         .send()
                         .await
@@ -112,7 +126,7 @@ impl<'a> Service<'a> {
             None => match config.zip.as_ref() {
                 Some(zip) => {
                     let url = format!("{GEO_URL}/zip?zip={zip}&appid={api_key}");
-                    let city: CityCoord = REQWEST_CLIENT
+                    let city: CityCoord = client
                         .get(url)
                         .send()
                         .await
@@ -165,6 +179,16 @@ struct ApiCurrentResponse {
     sys: ApiSys,
     name: String,
     dt: i64,
+    coord: ApiCoord,
+    /// Visibility in meters
+    #[serde(default)]
+    visibility: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiCoord {
+    lat: f64,
+    lon: f64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -222,7 +246,8 @@ impl WeatherProvider for Service<'_> {
             lang = self.lang,
         );
 
-        let current_data: ApiCurrentResponse = REQWEST_CLIENT
+        let current_data: ApiCurrentResponse = self
+            .client
             .get(current_url)
             .send()
             .await
@@ -231,6 +256,14 @@ impl WeatherProvider for Service<'_> {
             .await
             .error("Current weather request failed")?;
 
+        let to_kmh = |speed: f64| {
+            speed
+                * match self.units {
+                    UnitSystem::Metric => 3.6,
+                    UnitSystem::Imperial => 3.6 * 0.447,
+                }
+        };
+
         let current_weather = {
             let is_night = current_data.sys.sunrise >= current_data.dt
                 || current_data.dt >= current_data.sys.sunset;
@@ -238,15 +271,21 @@ impl WeatherProvider for Service<'_> {
                 temp: current_data.main.temp,
                 apparent: current_data.main.feels_like,
                 humidity: current_data.main.humidity,
+                dewpoint: None,
+                pressure: None,
+                visibility: current_data
+                    .visibility
+                    .map(|meters| self.units.distance_from_meters(meters)),
                 weather: current_data.weather[0].main.clone(),
                 weather_verbose: current_data.weather[0].description.clone(),
-                wind: current_data.wind.speed,
-                wind_kmh: current_data.wind.speed
-                    * match self.units {
-                        UnitSystem::Metric => 3.6,
-                        UnitSystem::Imperial => 3.6 * 0.447,
-                    },
+                weather_detailed: None,
+                wind: self.units.speed_from_kmh(to_kmh(current_data.wind.speed)),
+                wind_kmh: to_kmh(current_data.wind.speed),
+                wind_mph: to_kmh(current_data.wind.speed) / MPH_TO_KPH,
                 wind_direction: current_data.wind.deg,
+                wind_gust: None,
+                wind_gust_kmh: None,
+                precip_prob: None,
                 icon: weather_to_icon(current_data.weather[0].main.as_str(), is_night),
             }
         };
@@ -266,7 +305,8 @@ impl WeatherProvider for Service<'_> {
                 cnt = self.forecast_hours / 3,
             );
 
-            let forecast_data: ApiForecastResponse = REQWEST_CLIENT
+            let forecast_data: ApiForecastResponse = self
+                .client
                 .get(forecast_url)
                 .send()
                 .await
@@ -336,60 +376,91 @@ impl WeatherProvider for Service<'_> {
                     temp: temp_avg,
                     apparent: apparent_avg,
                     humidity: humidity_avg,
-                    wind: wind_avg,
-                    wind_kmh: wind_avg
-                        * match self.units {
-                            UnitSystem::Metric => 3.6,
-                            UnitSystem::Imperial => 3.6 * 0.447,
-                        },
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: self.units.speed_from_kmh(to_kmh(wind_avg)),
+                    wind_kmh: to_kmh(wind_avg),
+                    wind_mph: to_kmh(wind_avg) / MPH_TO_KPH,
                     wind_direction: direction_avg,
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                    start: None,
                 },
                 min: ForecastAggregate {
                     temp: temp_min,
                     apparent: apparent_min,
                     humidity: humidity_min,
-                    wind: *wind_min,
-                    wind_kmh: wind_min
-                        * match self.units {
-                            UnitSystem::Metric => 3.6,
-                            UnitSystem::Imperial => 3.6 * 0.447,
-                        },
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: self.units.speed_from_kmh(to_kmh(*wind_min)),
+                    wind_kmh: to_kmh(*wind_min),
+                    wind_mph: to_kmh(*wind_min) / MPH_TO_KPH,
                     wind_direction: *direction_min,
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                    start: None,
                 },
                 max: ForecastAggregate {
                     temp: temp_max,
                     apparent: apparent_max,
                     humidity: humidity_max,
-                    wind: *wind_max,
-                    wind_kmh: wind_max
-                        * match self.units {
-                            UnitSystem::Metric => 3.6,
-                            UnitSystem::Imperial => 3.6 * 0.447,
-                        },
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: self.units.speed_from_kmh(to_kmh(*wind_max)),
+                    wind_kmh: to_kmh(*wind_max),
+                    wind_mph: to_kmh(*wind_max) / MPH_TO_KPH,
                     wind_direction: *direction_max,
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                    start: None,
                 },
-                fin: WeatherMoment {
+                fin: Some(WeatherMoment {
                     icon: weather_to_icon(fin_data.weather[0].main.as_str(), fin_is_night),
                     weather: fin_data.weather[0].main.clone(),
                     weather_verbose: fin_data.weather[0].description.clone(),
+                    weather_detailed: None,
                     temp: fin_data.main.temp,
                     apparent: fin_data.main.feels_like,
                     humidity: fin_data.main.humidity,
-                    wind: fin_data.wind.speed,
-                    wind_kmh: fin_data.wind.speed
-                        * match self.units {
-                            UnitSystem::Metric => 3.6,
-                            UnitSystem::Imperial => 3.6 * 0.447,
-                        },
+                    dewpoint: None,
+                    pressure: None,
+                    visibility: None,
+                    wind: self.units.speed_from_kmh(to_kmh(fin_data.wind.speed)),
+                    wind_kmh: to_kmh(fin_data.wind.speed),
+                    wind_mph: to_kmh(fin_data.wind.speed) / MPH_TO_KPH,
                     wind_direction: fin_data.wind.deg,
-                },
+                    wind_gust: None,
+                    wind_gust_kmh: None,
+                    precip_prob: None,
+                }),
+                count: forecast_data.list.len(),
+                precip_any: None,
             })
         };
 
         Ok(WeatherResult {
             location: current_data.name,
+            location_distance: None,
+            location_bearing: None,
             current_weather,
             forecast,
+            alerts: None,
+            updated: None,
+            periods: Vec::new(),
+            coordinates: Some((current_data.coord.lat, current_data.coord.lon)),
+            temp_unit: self.units.temp_unit(),
+            pressure_unit: self.units.pressure_unit(),
+            units: *self.units,
+            provider: "openweathermap",
+            debug_stats: None,
+            daily: None,
+            grid_point: None,
         })
     }
 }
@@ -401,7 +472,7 @@ fn weather_to_icon(weather: &str, is_night: bool) -> WeatherIcon {
         "Clouds" => WeatherIcon::Clouds { is_night },
         "Fog" | "Mist" => WeatherIcon::Fog { is_night },
         "Thunderstorm" => WeatherIcon::Thunder { is_night },
-        "Snow" => WeatherIcon::Snow,
+        "Snow" => WeatherIcon::Snow { is_night },
         _ => WeatherIcon::Default,
     }
 }
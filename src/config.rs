@@ -42,6 +42,8 @@ pub struct SharedConfig {
     pub icons: Arc<Icons>,
     #[serde(default = "default_icons_format")]
     pub icons_format: Arc<String>,
+    #[serde(default = "default_decimal_separator")]
+    pub decimal_separator: char,
 }
 
 impl Default for SharedConfig {
@@ -50,6 +52,7 @@ impl Default for SharedConfig {
             theme: Default::default(),
             icons: Default::default(),
             icons_format: default_icons_format(),
+            decimal_separator: default_decimal_separator(),
         }
     }
 }
@@ -66,6 +69,10 @@ fn default_icons_format() -> Arc<String> {
     Arc::new("{icon}".into())
 }
 
+fn default_decimal_separator() -> char {
+    '.'
+}
+
 impl SharedConfig {
     pub fn get_icon(&self, icon: &str, value: Option<f64>) -> Result<String> {
         if icon.is_empty() {
@@ -95,6 +102,7 @@ pub struct CommonBlockConfig {
     pub click: ClickHandler,
     pub signal: Option<i32>,
     pub icons_format: Option<String>,
+    pub decimal_separator: Option<char>,
     pub theme_overrides: Option<ThemeOverrides>,
     pub icons_overrides: Option<HashMap<String, Icon>>,
     pub merge_with_next: bool,
@@ -119,7 +119,7 @@ impl EngFormatter {
 }
 
 impl Formatter for EngFormatter {
-    fn format(&self, val: &Value, _config: &SharedConfig) -> Result<String, FormatError> {
+    fn format(&self, val: &Value, config: &SharedConfig) -> Result<String, FormatError> {
         match val {
             Value::Number { mut val, mut unit } => {
                 if !self.range.contains(&val) {
@@ -165,7 +165,9 @@ impl Formatter for EngFormatter {
                 let mut retval = match self.width as i32 - digits {
                     i32::MIN..=0 => format!("{sign}{}", val.round()),
                     1 => format!("{}{sign}{}", self.pad_with, val.round() as i64),
-                    rest => format!("{sign}{val:.*}", rest as usize - 1),
+                    // Only this number has a decimal point, unlike the padding, prefix and unit
+                    rest => format!("{sign}{val:.*}", rest as usize - 1)
+                        .replace('.', config.decimal_separator.encode_utf8(&mut [0; 4])),
                 };
 
                 let display_prefix =
@@ -283,6 +285,42 @@ mod tests {
         assert_eq!(result, "321.6GB");
     }
 
+    #[test]
+    fn eng_decimal_separator() {
+        let config = SharedConfig {
+            decimal_separator: ',',
+            ..Default::default()
+        };
+        let val = Value::Number {
+            val: 18.5,
+            unit: Unit::Degrees,
+        };
+
+        let fmt = new_fmt!(eng, w: 4).unwrap();
+        assert_eq!(fmt.format(&val, &config).unwrap(), "18,5°");
+
+        let fmt = new_fmt!(eng, w: 2).unwrap();
+        assert_eq!(fmt.format(&val, &config).unwrap(), "19°");
+
+        // Only the decimal point is replaced, not the padding
+        let fmt = EngFormatter::from_args(&[
+            Arg {
+                key: "width",
+                val: "3",
+            },
+            Arg {
+                key: "pad_with",
+                val: ".",
+            },
+        ])
+        .unwrap();
+        let val = Value::Number {
+            val: 18.0,
+            unit: Unit::Degrees,
+        };
+        assert_eq!(fmt.format(&val, &config).unwrap(), ".18°");
+    }
+
     #[test]
     fn eng_prefixes() {
         let config = SharedConfig::default();
@@ -103,8 +103,13 @@ impl Default for Icons {
             "weather_fog_night" => "FOG",
             "weather_fog" => "FOG",
             "weather_moon" => "MOONY",
+            "weather_partly_cloudy_night" => "PARTLY CLOUDY",
+            "weather_partly_cloudy" => "PARTLY CLOUDY",
             "weather_rain_night" => "RAIN",
             "weather_rain" => "RAIN",
+            "weather_sleet_night" => "SLEET",
+            "weather_sleet" => "SLEET",
+            "weather_snow_night" => "SNOW",
             "weather_snow" => "SNOW",
             "weather_sun" => "SUNNY",
             "weather_thunder_night" => "STORM",
@@ -101,6 +101,9 @@ pub struct CliArgs {
     /// The maximum number of blocking threads spawned by tokio
     #[clap(long = "threads", short = 'j', default_value = "2")]
     pub blocking_threads: usize,
+    /// Fetch the weather once for each weather block, print the results and exit
+    #[clap(long = "check-weather")]
+    pub check_weather: bool,
 }
 
 pub struct BarState {
@@ -255,6 +258,9 @@ impl BarState {
         if let Some(icons_format) = block_config.common.icons_format {
             shared_config.icons_format = Arc::new(icons_format);
         }
+        if let Some(decimal_separator) = block_config.common.decimal_separator {
+            shared_config.decimal_separator = decimal_separator;
+        }
         if let Some(theme_overrides) = block_config.common.theme_overrides {
             Arc::make_mut(&mut shared_config.theme).apply_overrides(theme_overrides)?;
         }
@@ -1,6 +1,6 @@
 use clap::Parser;
 
-use i3status_rs::blocks::BlockError;
+use i3status_rs::blocks::{BlockConfig, BlockError};
 use i3status_rs::config::Config;
 use i3status_rs::errors::*;
 use i3status_rs::escape::Escaped;
@@ -16,11 +16,25 @@ enum ErrorMaybeInBlock {
 }
 
 fn main() {
-    env_logger::init();
-
     let args = i3status_rs::CliArgs::parse();
     let blocking_threads = args.blocking_threads;
 
+    if args.check_weather {
+        check_weather_logger().init();
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(check_weather(&args.config));
+        if let Err(error) = result {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    env_logger::init();
+
     if !args.no_init {
         protocol::init(args.never_pause);
     }
@@ -64,6 +78,51 @@ fn main() {
     }
 }
 
+/// Log everything the weather block does, in addition to what `RUST_LOG` enables
+fn check_weather_logger() -> env_logger::Builder {
+    let mut builder = env_logger::Builder::from_default_env();
+    // The block logs under its own "weather" target, while anything logged without one is
+    // attributed to the module
+    builder
+        .filter_module("weather", log::LevelFilter::Debug)
+        .filter_module("i3status_rs::blocks::weather", log::LevelFilter::Debug);
+    builder
+}
+
+/// Run the weather services of all weather blocks once, for `--check-weather`
+async fn check_weather(config: &str) -> Result<()> {
+    let config_path = util::find_file(config, None, Some("toml"))
+        .or_error(|| format!("Configuration file '{config}' not found"))?;
+    let config: Config = util::deserialize_toml_file(&config_path)?;
+    let mut checked = 0;
+    let mut failed = 0;
+    for (id, block) in config.blocks.iter().enumerate() {
+        match &block.config {
+            BlockConfig::weather(weather) => {
+                println!("block {id}:");
+                checked += 1;
+                if let Err(error) = i3status_rs::blocks::weather::check(weather).await {
+                    println!("block {id} failed: {error}");
+                    failed += 1;
+                }
+            }
+            BlockConfig::Err("weather", error) => {
+                println!("block {id}: configuration error: {error}");
+                checked += 1;
+                failed += 1;
+            }
+            _ => (),
+        }
+    }
+    match (checked, failed) {
+        (0, _) => Err(Error::new("No weather block configured")),
+        (_, 0) => Ok(()),
+        _ => Err(Error::new(format!(
+            "{failed} of {checked} weather blocks failed"
+        ))),
+    }
+}
+
 /// Restart in-place
 fn restart() -> ! {
     use std::env;
@@ -88,3 +147,25 @@ fn restart() -> ! {
     nix::unistd::execvp(&exe, &arg).unwrap();
     unreachable!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{Level, Log, Metadata};
+
+    #[test]
+    fn test_check_weather_logger() {
+        let logger = check_weather_logger().build();
+        let enabled = |target| {
+            logger.enabled(
+                &Metadata::builder()
+                    .target(target)
+                    .level(Level::Debug)
+                    .build(),
+            )
+        };
+        assert!(enabled("weather"));
+        assert!(enabled("i3status_rs::blocks::weather::nws"));
+        assert!(!enabled("i3status_rs::blocks::battery"));
+    }
+}